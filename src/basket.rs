@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::history::split_csv_line;
+use crate::output::{CpiLevels, IndexResult};
+use crate::series::{IndexPoint, PriceIndexSeries};
+
+// ----------------------- Basket file -----------------------
+/// One COICOP division of a personal basket (`--basket`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasketItem {
+    /// COICOP division, e.g. "CP04".
+    pub coicop: String,
+    /// Share of spending; normalised by [`read_basket`] so that all weights sum to 1.
+    pub weight: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Read a basket from a `.csv` or `.json` file (chosen by extension) and
+/// normalise its weights to shares.
+///
+/// CSV: `coicop,weight[,label]` rows, with an optional header line.
+/// JSON: an array of `{"coicop": ..., "weight": ..., "label": ...}` objects.
+/// Weights can be in any unit (percent, per mille, money spent).
+pub fn read_basket(path: &Path) -> Result<Vec<BasketItem>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let items = match ext.as_str() {
+        "csv" => parse_basket_csv(&text),
+        "json" => serde_json::from_str(&text).map_err(anyhow::Error::from),
+        _ => return Err(anyhow!("Unsupported basket file '{}' (use .csv or .json)", path.display())),
+    }
+    .and_then(normalize_basket)
+    .with_context(|| format!("Invalid basket file {}", path.display()))?;
+    Ok(items)
+}
+
+pub fn parse_basket_csv(text: &str) -> Result<Vec<BasketItem>> {
+    let mut items = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line);
+        let (coicop, weight) = match (fields.first(), fields.get(1)) {
+            (Some(c), Some(w)) => (c.trim(), w.trim()),
+            _ => return Err(anyhow!("Line {}: expected coicop,weight[,label]", i + 1)),
+        };
+        let weight: f64 = match weight.parse() {
+            Ok(w) => w,
+            // Header line.
+            Err(_) if items.is_empty() && coicop.eq_ignore_ascii_case("coicop") => continue,
+            Err(_) => return Err(anyhow!("Line {}: invalid weight '{}'", i + 1, weight)),
+        };
+        let label = fields.get(2).map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        items.push(BasketItem { coicop: coicop.to_string(), weight, label });
+    }
+    Ok(items)
+}
+
+/// Upper-case the codes, reject duplicates and non-positive weights, and
+/// scale the weights to sum to 1.
+pub fn normalize_basket(mut items: Vec<BasketItem>) -> Result<Vec<BasketItem>> {
+    if items.is_empty() {
+        return Err(anyhow!("Empty basket"));
+    }
+    for (i, item) in items.iter_mut().enumerate() {
+        item.coicop = item.coicop.trim().to_uppercase();
+        if item.coicop.is_empty() {
+            return Err(anyhow!("Item {}: missing COICOP division", i + 1));
+        }
+        if !(item.weight.is_finite() && item.weight > 0.0) {
+            return Err(anyhow!("{}: weight must be > 0 (got {})", item.coicop, item.weight));
+        }
+    }
+    for (i, item) in items.iter().enumerate() {
+        if items[..i].iter().any(|x| x.coicop == item.coicop) {
+            return Err(anyhow!("{} is listed twice", item.coicop));
+        }
+    }
+
+    let total: f64 = items.iter().map(|x| x.weight).sum();
+    for item in &mut items {
+        item.weight /= total;
+    }
+    Ok(items)
+}
+
+// ----------------------- Chain-linked index -----------------------
+/// Base level of a chain-linked index at its first period.
+pub const CHAIN_BASE_LEVEL: f64 = 100.0;
+
+/// Weighted index of several monthly series, chain-linked every December
+/// (the HICP convention): within a year each component is taken relative to
+/// the previous link month,
+///
+/// `I_t = I_link * Σ_i w_i * (P_i,t / P_i,link)`
+///
+/// and the first link is the first period all components share. Only the
+/// periods every component has are kept; `weight`s should sum to 1.
+pub fn chain_linked_index(country: &str, components: &[(f64, &PriceIndexSeries)]) -> Result<PriceIndexSeries> {
+    let (_, first) = components.first().ok_or_else(|| anyhow!("Empty basket"))?;
+    let levels: Vec<HashMap<&str, f64>> = components
+        .iter()
+        .map(|(_, s)| s.points.iter().map(|p| (p.period.as_str(), p.level)).collect())
+        .collect();
+
+    let mut points = Vec::new();
+    let mut link: Option<(f64, Vec<f64>)> = None;
+    for p in &first.points {
+        let Some(current) = levels.iter().map(|l| l.get(p.period.as_str()).copied()).collect::<Option<Vec<f64>>>()
+        else {
+            continue;
+        };
+        let (link_level, link_prices) = link.get_or_insert_with(|| (CHAIN_BASE_LEVEL, current.clone()));
+        let relative: f64 = components
+            .iter()
+            .zip(current.iter().zip(link_prices.iter()))
+            .map(|((w, _), (now, then))| w * now / then)
+            .sum();
+        let level = *link_level * relative;
+        points.push(IndexPoint { period: p.period.clone(), level });
+
+        if p.period.ends_with("-12") {
+            link = Some((level, current));
+        }
+    }
+
+    let start = points
+        .first()
+        .ok_or_else(|| anyhow!("The basket's divisions have no period in common"))?;
+    Ok(PriceIndexSeries {
+        country: country.to_string(),
+        start_period: start.period.clone(),
+        base_level: start.level,
+        yearly: Vec::new(),
+        segments: Vec::new(),
+        points,
+    })
+}
+
+// ----------------------- Basket vs headline -----------------------
+/// A personal basket index next to headline CPI over the same periods.
+#[derive(Debug, Clone)]
+pub struct BasketComparison {
+    /// Chain-linked personal index.
+    pub index: PriceIndexSeries,
+    pub personal: IndexResult,
+    pub headline: IndexResult,
+}
+
+/// Chain-link the `components` series (one per item of `basket`, in order)
+/// and compare `amount` over the personal index with headline CPI, both
+/// from the index's first to its latest period.
+pub fn compare_basket(
+    basket: &[BasketItem],
+    components: &[PriceIndexSeries],
+    headline: &PriceIndexSeries,
+    amount: f64,
+    inflate: bool,
+) -> Result<BasketComparison> {
+    let weighted: Vec<(f64, &PriceIndexSeries)> = basket.iter().map(|b| b.weight).zip(components).collect();
+    let index = chain_linked_index(&headline.country, &weighted)?;
+    let latest = index.latest().ok_or_else(|| anyhow!("No CPI data found"))?.clone();
+    let start = IndexPoint { period: index.start_period.clone(), level: index.base_level };
+
+    let headline_point = |period: &str| {
+        headline
+            .level_at(period)
+            .map(|level| IndexPoint { period: period.to_string(), level })
+            .ok_or_else(|| anyhow!("No headline CPI for {}", period))
+    };
+    let headline_levels = CpiLevels { start: headline_point(&start.period)?, latest: headline_point(&latest.period)? };
+
+    Ok(BasketComparison {
+        personal: IndexResult::new(CpiLevels { start, latest }, amount, inflate),
+        headline: IndexResult::new(headline_levels, amount, inflate),
+        index,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monthly(levels: &[(&str, f64)]) -> PriceIndexSeries {
+        let points: Vec<IndexPoint> =
+            levels.iter().map(|(p, l)| IndexPoint { period: p.to_string(), level: *l }).collect();
+        PriceIndexSeries {
+            country: "ITA".to_string(),
+            start_period: points[0].period.clone(),
+            base_level: points[0].level,
+            points,
+            yearly: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    fn item(coicop: &str, weight: f64) -> BasketItem {
+        BasketItem { coicop: coicop.to_string(), weight, label: None }
+    }
+
+    #[test]
+    fn basket_of_headline_divisions_matches_headline() {
+        let food = monthly(&[("2023-11", 100.0), ("2023-12", 110.0), ("2024-01", 121.0)]);
+        let rent = monthly(&[("2023-11", 100.0), ("2023-12", 100.0), ("2024-01", 100.0)]);
+        // Fixed 50/50 shares at the November prices, linked in December.
+        let headline = monthly(&[("2023-11", 200.0), ("2023-12", 210.0), ("2024-01", 220.5)]);
+
+        let c = compare_basket(&[item("CP01", 0.5), item("CP04", 0.5)], &[food, rent], &headline, 1000.0, false)
+            .unwrap();
+        assert_eq!(c.index.start_period, "2023-11");
+        assert_eq!(c.personal.levels.latest.period, "2024-01");
+        assert!((c.personal.result.deflator - 1.1025).abs() < 1e-12);
+        assert!((c.headline.result.deflator - 1.1025).abs() < 1e-12);
+        assert!(c.personal.inflated.is_none());
+    }
+
+    #[test]
+    fn basket_needs_headline_over_the_same_periods() {
+        let food = monthly(&[("2023-11", 100.0), ("2023-12", 110.0)]);
+        let headline = monthly(&[("2023-12", 200.0)]);
+        let err = compare_basket(&[item("CP01", 1.0)], &[food], &headline, 1000.0, true).unwrap_err();
+        assert!(err.to_string().contains("No headline CPI for 2023-11"));
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::cache::{meta_path, read_checked, read_meta, sha256_hex, write_atomic, Cache, EntryMeta};
+use crate::inventory::{kind_of, validate, CacheEntry, EntryKind};
+
+// ----------------------- Offline data bundles -----------------------
+// A bundle is one gzip-compressed JSON document holding cache entries
+// (codelists + canonical series) with their sidecar metadata, so analysts on
+// air-gapped machines can run RIP with `--offline`.
+pub const BUNDLE_FORMAT: &str = "rip-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    pub rip_version: String,
+    /// Countries requested at export time (empty = everything cached).
+    pub countries: Vec<String>,
+    pub entries: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleEntry {
+    /// Cache entry name, e.g. "series/sdmx/CPI/ITA.CPI._T.IX.M.json".
+    pub name: String,
+    pub meta: EntryMeta,
+    pub content: String,
+}
+
+/// Country a series entry belongs to, from its key
+/// ("series/sdmx/CPI/ITA.CPI._T.IX.M.json" or "series/datamapper/PCPIPCH/ITA.json").
+fn series_country(name: &str) -> Option<String> {
+    let file = name.rsplit('/').next()?.strip_suffix(".json")?;
+    file.split('.').next().map(|c| c.to_uppercase())
+}
+
+fn wanted(entry: &CacheEntry, countries: &[String]) -> bool {
+    match entry.kind {
+        EntryKind::SdmxCodelist | EntryKind::SdmxStructure | EntryKind::DataMapperCountries => true,
+        EntryKind::Series => {
+            countries.is_empty()
+                || series_country(&entry.name).is_some_and(|c| countries.iter().any(|x| x.eq_ignore_ascii_case(&c)))
+        }
+        EntryKind::Other => false,
+    }
+}
+
+/// Collect the codelists and the cached series of `countries` (all if empty).
+pub fn export(cache: &Cache, countries: &[String]) -> Result<Bundle> {
+    let mut entries = Vec::new();
+    for e in cache.entries()?.iter().filter(|e| wanted(e, countries)) {
+        let b = read_checked(&e.path).with_context(|| format!("Cache entry {} is corrupt", e.name))?;
+        let meta = read_meta(&e.path).unwrap_or_else(|| EntryMeta {
+            url: String::new(),
+            fetched_at: e.fetched_at.unwrap_or_else(Utc::now),
+            sha256: sha256_hex(&b),
+            size: b.len() as u64,
+        });
+        let content = String::from_utf8(b).with_context(|| format!("Cache entry {} is not text", e.name))?;
+        entries.push(BundleEntry {
+            name: e.name.clone(),
+            meta,
+            content,
+        });
+    }
+
+    Ok(Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created_at: Utc::now(),
+        rip_version: env!("CARGO_PKG_VERSION").to_string(),
+        countries: countries.iter().map(|c| c.to_uppercase()).collect(),
+        entries,
+    })
+}
+
+/// Countries of the series contained in a bundle.
+pub fn bundle_countries(bundle: &Bundle) -> Vec<String> {
+    let mut out: Vec<String> = bundle
+        .entries
+        .iter()
+        .filter(|e| kind_of(&e.name) == EntryKind::Series)
+        .filter_map(|e| series_country(&e.name))
+        .collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+pub fn write_bundle(bundle: &Bundle, path: &Path) -> Result<()> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    gz.write_all(&serde_json::to_vec(bundle)?)?;
+    write_atomic(path, &gz.finish()?)
+}
+
+/// Read a bundle (gzip-compressed or plain JSON) and check its format and version.
+pub fn read_bundle(path: &Path) -> Result<Bundle> {
+    let raw = fs::read(path).with_context(|| format!("Cannot read bundle {}", path.display()))?;
+    let json = if raw.starts_with(&[0x1f, 0x8b]) {
+        let mut out = Vec::new();
+        GzDecoder::new(raw.as_slice())
+            .read_to_end(&mut out)
+            .context("Corrupt bundle (gzip)")?;
+        out
+    } else {
+        raw
+    };
+
+    let bundle: Bundle = serde_json::from_slice(&json).context("Corrupt bundle (JSON)")?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(anyhow!("Not a RIP bundle (format '{}')", bundle.format));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(anyhow!(
+            "Bundle version {} is newer than supported ({}); upgrade RIP",
+            bundle.version,
+            BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Write every bundle entry into the cache dir (validated, atomically, with
+/// the original sidecar metadata). Returns the number of entries imported.
+pub fn import(cache: &Cache, bundle: &Bundle) -> Result<usize> {
+    for e in &bundle.entries {
+        let kind = kind_of(&e.name);
+        if kind == EntryKind::Other || e.name.split('/').any(|seg| seg.is_empty() || seg == "..") {
+            return Err(anyhow!("Unexpected entry '{}' in bundle", e.name));
+        }
+        let bytes = e.content.as_bytes();
+        if sha256_hex(bytes) != e.meta.sha256 {
+            return Err(anyhow!("Bundle entry {} does not match its hash", e.name));
+        }
+        validate(kind, bytes).with_context(|| format!("Bundle entry {} is invalid", e.name))?;
+    }
+
+    for e in &bundle.entries {
+        let path = cache.dir().join(&e.name);
+        write_atomic(&path, e.content.as_bytes())?;
+        write_atomic(&meta_path(&path), &serde_json::to_vec_pretty(&e.meta)?)?;
+    }
+    Ok(bundle.entries.len())
+}
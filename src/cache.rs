@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+// ----------------------- Cache dir -----------------------
+pub fn default_cache_dir() -> Result<PathBuf> {
+    let mut dir = dirs::cache_dir().ok_or_else(|| anyhow!("Could not locate a cache directory"))?;
+    dir.push("rip");
+    Ok(dir)
+}
+
+// ----------------------- Policy -----------------------
+/// How cached responses are used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CachePolicy {
+    /// Serve fresh entries (within TTL) from disk, fetch and store the rest.
+    #[default]
+    Use,
+    /// Always fetch, then overwrite the cached entry.
+    Refresh,
+    /// Neither read nor write the cache.
+    Off,
+    /// Only read the cache (ignoring TTLs); never touch the network.
+    Offline,
+}
+
+impl FromStr for CachePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "use" => Ok(Self::Use),
+            "refresh" => Ok(Self::Refresh),
+            "off" => Ok(Self::Off),
+            "offline" => Ok(Self::Offline),
+            _ => Err(anyhow!("Unknown cache policy '{}' (expected use, refresh, off or offline)", s)),
+        }
+    }
+}
+
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Use => "use",
+            Self::Refresh => "refresh",
+            Self::Off => "off",
+            Self::Offline => "offline",
+        };
+        f.write_str(s)
+    }
+}
+
+// ----------------------- TTLs -----------------------
+/// Kind of cached resource; each has its own time-to-live.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// Country codelists: change rarely.
+    Codelist,
+    /// Data queries whose range includes the current period: a new print may land any day.
+    CurrentData,
+    /// Data queries entirely in the past: only touched by revisions.
+    HistoricalData,
+}
+
+impl Resource {
+    /// Data resource kind for a query ending at `end` (compared with `current`,
+    /// both in the same sortable period format).
+    pub fn data(end: &str, current: &str) -> Self {
+        if end >= current {
+            Self::CurrentData
+        } else {
+            Self::HistoricalData
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CacheTtls {
+    pub codelist: Duration,
+    pub current_data: Duration,
+    pub historical_data: Duration,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            codelist: Duration::from_secs(30 * 86_400),
+            current_data: Duration::from_secs(12 * 3_600),
+            historical_data: Duration::from_secs(7 * 86_400),
+        }
+    }
+}
+
+impl CacheTtls {
+    pub fn ttl(&self, resource: Resource) -> Duration {
+        match resource {
+            Resource::Codelist => self.codelist,
+            Resource::CurrentData => self.current_data,
+            Resource::HistoricalData => self.historical_data,
+        }
+    }
+}
+
+/// Parse durations like "500ms", "90s", "45m", "12h", "30d" or "2w" (a bare number means seconds).
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let t = s.trim();
+    let split = t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: u64 = num
+        .parse()
+        .with_context(|| format!("Invalid duration '{}' (expected e.g. 12h, 30d)", s))?;
+
+    let secs = match unit.trim() {
+        "ms" => return Ok(Duration::from_millis(n)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        u => return Err(anyhow!("Unknown duration unit '{}' in '{}' (use ms, s, m, h, d or w)", u, s)),
+    };
+    Ok(Duration::from_secs(n * secs))
+}
+
+// ----------------------- Config -----------------------
+/// `[cache]` table of the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    pub policy: Option<CachePolicy>,
+    pub codelist_ttl: Option<String>,
+    pub current_data_ttl: Option<String>,
+    pub historical_data_ttl: Option<String>,
+}
+
+impl CacheConfig {
+    pub fn ttls(&self) -> Result<CacheTtls> {
+        let mut ttls = CacheTtls::default();
+        if let Some(s) = &self.codelist_ttl {
+            ttls.codelist = parse_duration(s).context("cache.codelist_ttl")?;
+        }
+        if let Some(s) = &self.current_data_ttl {
+            ttls.current_data = parse_duration(s).context("cache.current_data_ttl")?;
+        }
+        if let Some(s) = &self.historical_data_ttl {
+            ttls.historical_data = parse_duration(s).context("cache.historical_data_ttl")?;
+        }
+        Ok(ttls)
+    }
+}
+
+// ----------------------- Entry files -----------------------
+/// Sidecar written next to every cache entry as `<entry>.meta.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryMeta {
+    pub url: String,
+    pub fetched_at: DateTime<Utc>,
+    pub sha256: String,
+    pub size: u64,
+}
+
+pub const META_SUFFIX: &str = ".meta.json";
+
+pub fn meta_path(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_os_string();
+    s.push(META_SUFFIX);
+    PathBuf::from(s)
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Write to a temp file in the same directory, then rename over `path`, so
+/// readers never see a half-written file.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow!("Invalid cache path {}", path.display()))?;
+    fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!(".{}.tmp-{}", file_name, std::process::id()));
+    fs::write(&tmp, bytes).with_context(|| format!("Cannot write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| {
+        let _ = fs::remove_file(&tmp);
+        format!("Cannot move {} into place", path.display())
+    })
+}
+
+pub fn read_meta(path: &Path) -> Option<EntryMeta> {
+    let b = fs::read(meta_path(path)).ok()?;
+    serde_json::from_slice(&b).ok()
+}
+
+/// Entry bytes, checked against the sidecar hash when there is one.
+pub fn read_checked(path: &Path) -> Result<Vec<u8>> {
+    let b = fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    if let Some(meta) = read_meta(path) {
+        if meta.sha256 != sha256_hex(&b) {
+            return Err(anyhow!("Content hash mismatch (file changed or truncated after it was fetched)"));
+        }
+    }
+    Ok(b)
+}
+
+// ----------------------- Cache -----------------------
+/// On-disk response cache shared by the providers.
+///
+/// Callers validate (parse) a payload before [`Cache::write`], and
+/// [`Cache::evict`] entries that no longer parse, so error pages and
+/// truncated downloads never stick.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    policy: CachePolicy,
+    ttls: CacheTtls,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, policy: CachePolicy, ttls: CacheTtls) -> Self {
+        Self { dir, policy, ttls }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    pub fn ttls(&self) -> &CacheTtls {
+        &self.ttls
+    }
+
+    /// Cached bytes for `name`, if the policy allows reading and the entry is
+    /// fresh enough for `resource` (offline mode ignores age).
+    pub fn read(&self, name: &str, resource: Resource) -> Option<Vec<u8>> {
+        let file = self.dir.join(name);
+        match self.policy {
+            CachePolicy::Off | CachePolicy::Refresh => None,
+            CachePolicy::Offline => self.read_or_evict(name),
+            CachePolicy::Use => {
+                let fetched_at = match read_meta(&file) {
+                    Some(m) => SystemTime::from(m.fetched_at),
+                    None => fs::metadata(&file).and_then(|m| m.modified()).ok()?,
+                };
+                let age = SystemTime::now().duration_since(fetched_at).unwrap_or_default();
+                if age > self.ttls.ttl(resource) {
+                    return None;
+                }
+                self.read_or_evict(name)
+            }
+        }
+    }
+
+    /// Cached bytes for `name` regardless of age (for stores that manage
+    /// freshness themselves); `None` when the cache is off.
+    pub fn read_stored(&self, name: &str) -> Option<Vec<u8>> {
+        match self.policy {
+            CachePolicy::Off => None,
+            _ => self.read_or_evict(name),
+        }
+    }
+
+    fn read_or_evict(&self, name: &str) -> Option<Vec<u8>> {
+        let file = self.dir.join(name);
+        if !file.is_file() {
+            return None;
+        }
+        match read_checked(&file) {
+            Ok(b) => Some(b),
+            Err(_) => {
+                self.evict(name);
+                None
+            }
+        }
+    }
+
+    /// Atomically store an already validated payload under `name`, with a
+    /// sidecar recording `url`, fetch time and content hash.
+    pub fn write(&self, name: &str, bytes: &[u8], url: &str) -> Result<()> {
+        if !matches!(self.policy, CachePolicy::Use | CachePolicy::Refresh) {
+            return Ok(());
+        }
+        let file = self.dir.join(name);
+        let meta = EntryMeta {
+            url: url.to_string(),
+            fetched_at: Utc::now(),
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+        };
+        write_atomic(&file, bytes)?;
+        write_atomic(&meta_path(&file), &serde_json::to_vec_pretty(&meta)?)
+    }
+
+    /// Sidecar metadata of an entry, if it has one.
+    pub fn meta(&self, name: &str) -> Option<EntryMeta> {
+        read_meta(&self.dir.join(name))
+    }
+
+    /// Drop an entry (and its sidecar) that failed to parse, so it is refetched.
+    pub fn evict(&self, name: &str) {
+        let file = self.dir.join(name);
+        let _ = fs::remove_file(meta_path(&file));
+        let _ = fs::remove_file(file);
+    }
+
+    /// Fails in offline mode, where a cache miss cannot fall back to the network.
+    pub fn ensure_online(&self, what: &str) -> Result<()> {
+        if self.policy == CachePolicy::Offline {
+            return Err(anyhow!(
+                "Offline: {} is not in the local data ({}). Import a bundle that contains it (rip bundle import) or run online.",
+                what,
+                self.dir.display()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+
+use crate::cache::write_atomic;
+use crate::font::{glyph, GLYPH_H, GLYPH_W};
+
+// ----------------------- Chart model -----------------------
+/// One line of a chart: `(period, value)` points in chronological order.
+#[derive(Debug, Clone)]
+pub struct ChartLine {
+    pub label: String,
+    pub points: Vec<(String, f64)>,
+    /// Drawn dashed and grey (reference lines such as the nominal amount).
+    pub reference: bool,
+}
+
+/// Line chart over periods; the time axis is the sorted union of every
+/// line's periods, so countries with different coverage share one axis.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    pub title: String,
+    pub y_label: String,
+    pub lines: Vec<ChartLine>,
+}
+
+pub const CHART_WIDTH: u32 = 960;
+pub const CHART_HEIGHT: u32 = 540;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Rgb(u8, u8, u8);
+
+impl Rgb {
+    fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+const BACKGROUND: Rgb = Rgb(255, 255, 255);
+const INK: Rgb = Rgb(40, 40, 40);
+const GRID: Rgb = Rgb(225, 225, 225);
+const REFERENCE: Rgb = Rgb(130, 130, 130);
+const PALETTE: [Rgb; 6] = [
+    Rgb(31, 119, 180),
+    Rgb(214, 39, 40),
+    Rgb(44, 160, 44),
+    Rgb(255, 127, 14),
+    Rgb(148, 103, 189),
+    Rgb(140, 86, 75),
+];
+
+impl Chart {
+    /// Write the chart as SVG or PNG, chosen by the extension of `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let bytes = match ext.as_str() {
+            "svg" => self.to_svg().into_bytes(),
+            "png" => self.to_png()?,
+            _ => return Err(anyhow!("Unsupported chart file '{}' (use .svg or .png)", path.display())),
+        };
+        write_atomic(path, &bytes).with_context(|| format!("Cannot write chart {}", path.display()))
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut c = SvgCanvas::new(CHART_WIDTH, CHART_HEIGHT);
+        self.draw(&mut c);
+        c.finish()
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut c = PixelCanvas::new(CHART_WIDTH, CHART_HEIGHT);
+        self.draw(&mut c);
+        c.encode_png()
+    }
+
+    fn periods(&self) -> Vec<&str> {
+        let set: BTreeSet<&str> = self
+            .lines
+            .iter()
+            .flat_map(|l| l.points.iter().map(|(p, _)| p.as_str()))
+            .collect();
+        set.into_iter().collect()
+    }
+
+    fn draw(&self, c: &mut dyn Canvas) {
+        let (w, h) = (CHART_WIDTH as f64, CHART_HEIGHT as f64);
+        c.rect(0.0, 0.0, w, h, BACKGROUND);
+        c.text(w / 2.0, 24.0, &self.title, Anchor::Middle, true, INK);
+
+        let periods = self.periods();
+        let values: Vec<f64> = self.lines.iter().flat_map(|l| l.points.iter().map(|(_, v)| *v)).collect();
+        if periods.is_empty() || values.is_empty() {
+            c.text(w / 2.0, h / 2.0, "No data", Anchor::Middle, false, INK);
+            return;
+        }
+
+        let ticks = nice_ticks(
+            values.iter().copied().fold(f64::INFINITY, f64::min),
+            values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        );
+        let (y_min, y_max) = (ticks[0], ticks[ticks.len() - 1]);
+        let tick_labels: Vec<String> = ticks.iter().map(|t| fmt_tick(*t)).collect();
+        let label_w = tick_labels.iter().map(|l| c.text_width(l, false)).fold(0.0, f64::max);
+
+        // Plot area.
+        let left = 20.0 + label_w;
+        let right = w - 24.0;
+        let top = 76.0;
+        let bottom = h - 86.0;
+
+        let x_of = |i: usize| {
+            if periods.len() == 1 {
+                (left + right) / 2.0
+            } else {
+                left + (right - left) * i as f64 / (periods.len() - 1) as f64
+            }
+        };
+        let y_of = |v: f64| bottom - (bottom - top) * (v - y_min) / (y_max - y_min);
+
+        // Value axis: grid lines and labels.
+        for (t, label) in ticks.iter().zip(&tick_labels) {
+            let y = y_of(*t);
+            c.line((left, y), (right, y), GRID, 1.0, false);
+            c.text(left - 8.0, y, label, Anchor::End, false, INK);
+        }
+        c.text(left, top - 22.0, &self.y_label, Anchor::Start, false, INK);
+
+        // Time axis: first and last period always labelled, a few in between.
+        c.line((left, bottom), (right, bottom), INK, 1.0, false);
+        let label_px = c.text_width(periods[0], false) + 24.0;
+        let max_labels = (((right - left) / label_px) as usize).max(2);
+        let step = periods.len().div_ceil(max_labels).max(1);
+        for (i, p) in periods.iter().enumerate() {
+            let last = i == periods.len() - 1;
+            let near_last = !last && periods.len() - 1 - i < step;
+            if (i % step == 0 && !near_last) || last {
+                let x = x_of(i);
+                // Keep the outermost labels inside the image.
+                let anchor = match i {
+                    0 if periods.len() > 1 => Anchor::Start,
+                    _ if last && periods.len() > 1 => Anchor::End,
+                    _ => Anchor::Middle,
+                };
+                c.line((x, bottom), (x, bottom + 5.0), INK, 1.0, false);
+                c.text(x, bottom + 18.0, p, anchor, false, INK);
+            }
+        }
+
+        // Lines.
+        let mut legend = Vec::new();
+        let mut palette = PALETTE.iter().cycle();
+        for line in &self.lines {
+            let color = if line.reference { REFERENCE } else { *palette.next().unwrap_or(&INK) };
+            let pts: Vec<(f64, f64)> = line
+                .points
+                .iter()
+                .filter_map(|(p, v)| periods.binary_search(&p.as_str()).ok().map(|i| (x_of(i), y_of(*v))))
+                .collect();
+            for seg in pts.windows(2) {
+                c.line(seg[0], seg[1], color, 2.5, line.reference);
+            }
+            if let (Some(first), Some(last)) = (pts.first(), pts.last()) {
+                c.dot(first.0, first.1, 4.0, color);
+                c.dot(last.0, last.1, 4.0, color);
+            }
+            legend.push((line.label.as_str(), color, line.reference));
+        }
+
+        // Legend below the time axis.
+        let mut x = left;
+        let y = h - 30.0;
+        for (label, color, dashed) in legend {
+            c.line((x, y), (x + 26.0, y), color, 2.5, dashed);
+            c.text(x + 32.0, y, label, Anchor::Start, false, INK);
+            x += 32.0 + c.text_width(label, false) + 28.0;
+        }
+    }
+}
+
+/// Round tick values (steps of 1, 2 or 5 × 10^k) covering `[min, max]`.
+fn nice_ticks(min: f64, max: f64) -> Vec<f64> {
+    let (min, max) = if (max - min).abs() < f64::EPSILON {
+        (min - 1.0_f64.max(min.abs() * 0.05), max + 1.0_f64.max(max.abs() * 0.05))
+    } else {
+        (min, max)
+    };
+    let raw = (max - min) / 5.0;
+    let mag = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * mag)
+        .find(|s| *s >= raw)
+        .unwrap_or(10.0 * mag);
+
+    let start = (min / step).floor() * step;
+    let mut ticks = vec![start];
+    while ticks.len() < 2 || ticks[ticks.len() - 1] < max - step * 1e-9 {
+        ticks.push(start + step * ticks.len() as f64);
+    }
+    ticks
+}
+
+fn fmt_tick(v: f64) -> String {
+    if v.abs() >= 100.0 || v.fract().abs() < 1e-9 {
+        format!("{:.0}", v)
+    } else {
+        format!("{:.2}", v)
+    }
+}
+
+// ----------------------- Backends -----------------------
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Drawing primitives shared by the SVG and PNG backends; `y` of text is
+/// its vertical centre.
+trait Canvas {
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, color: Rgb);
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: Rgb, width: f64, dashed: bool);
+    fn dot(&mut self, x: f64, y: f64, r: f64, color: Rgb);
+    fn text(&mut self, x: f64, y: f64, s: &str, anchor: Anchor, title: bool, color: Rgb);
+    fn text_width(&self, s: &str, title: bool) -> f64;
+}
+
+// ---- SVG ----
+struct SvgCanvas {
+    out: String,
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl SvgCanvas {
+    fn new(w: u32, h: u32) -> Self {
+        Self {
+            out: format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+                 font-family=\"DejaVu Sans, Helvetica, Arial, sans-serif\">\n"
+            ),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.out.push_str("</svg>\n");
+        self.out
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, color: Rgb) {
+        self.out.push_str(&format!(
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>\n",
+            x,
+            y,
+            w,
+            h,
+            color.hex()
+        ));
+    }
+
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: Rgb, width: f64, dashed: bool) {
+        self.out.push_str(&format!(
+            "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"{}\"{} stroke-linecap=\"round\"/>\n",
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            color.hex(),
+            width,
+            if dashed { " stroke-dasharray=\"8 6\"" } else { "" }
+        ));
+    }
+
+    fn dot(&mut self, x: f64, y: f64, r: f64, color: Rgb) {
+        self.out.push_str(&format!(
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\"/>\n",
+            x,
+            y,
+            r,
+            color.hex()
+        ));
+    }
+
+    fn text(&mut self, x: f64, y: f64, s: &str, anchor: Anchor, title: bool, color: Rgb) {
+        let anchor = match anchor {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+            Anchor::End => "end",
+        };
+        self.out.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"middle\" fill=\"{}\">{}</text>\n",
+            x,
+            y,
+            if title { 18 } else { 12 },
+            anchor,
+            color.hex(),
+            xml_escape(s)
+        ));
+    }
+
+    fn text_width(&self, s: &str, title: bool) -> f64 {
+        // Average advance of a sans-serif font at 12px / 18px.
+        s.chars().count() as f64 * if title { 10.5 } else { 7.0 }
+    }
+}
+
+// ---- PNG (raster + bundled bitmap font) ----
+struct PixelCanvas {
+    w: usize,
+    h: usize,
+    rgb: Vec<u8>,
+}
+
+impl PixelCanvas {
+    fn new(w: u32, h: u32) -> Self {
+        let (w, h) = (w as usize, h as usize);
+        Self { w, h, rgb: vec![255; w * h * 3] }
+    }
+
+    fn put(&mut self, x: i64, y: i64, color: Rgb) {
+        if x < 0 || y < 0 || x as usize >= self.w || y as usize >= self.h {
+            return;
+        }
+        let i = (y as usize * self.w + x as usize) * 3;
+        self.rgb[i..i + 3].copy_from_slice(&[color.0, color.1, color.2]);
+    }
+
+    fn disc(&mut self, cx: f64, cy: f64, r: f64, color: Rgb) {
+        let r2 = r * r;
+        for y in (cy - r).floor() as i64..=(cy + r).ceil() as i64 {
+            for x in (cx - r).floor() as i64..=(cx + r).ceil() as i64 {
+                let (dx, dy) = (x as f64 + 0.5 - cx, y as f64 + 0.5 - cy);
+                if dx * dx + dy * dy <= r2 {
+                    self.put(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Glyph scale: 2× for labels, 3× for the title.
+    fn scale(title: bool) -> usize {
+        if title {
+            3
+        } else {
+            2
+        }
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>> {
+        fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(kind);
+            crc.update(data);
+            out.extend_from_slice(kind);
+            out.extend_from_slice(data);
+            out.extend_from_slice(&crc.finalize().to_be_bytes());
+        }
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.w as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.h as u32).to_be_bytes());
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB, no interlace
+
+        // Every scanline starts with filter type 0 (none).
+        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.rgb.chunks(self.w * 3) {
+            z.write_all(&[0])?;
+            z.write_all(row)?;
+        }
+
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut out, b"IHDR", &ihdr);
+        chunk(&mut out, b"IDAT", &z.finish()?);
+        chunk(&mut out, b"IEND", &[]);
+        Ok(out)
+    }
+}
+
+impl Canvas for PixelCanvas {
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, color: Rgb) {
+        for py in y as i64..(y + h) as i64 {
+            for px in x as i64..(x + w) as i64 {
+                self.put(px, py, color);
+            }
+        }
+    }
+
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: Rgb, width: f64, dashed: bool) {
+        let len = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let steps = (len * 2.0).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let d = len * i as f64 / steps as f64;
+            if dashed && d % 14.0 > 8.0 {
+                continue;
+            }
+            let t = i as f64 / steps as f64;
+            let (x, y) = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            if width <= 1.0 {
+                self.put(x.floor() as i64, y.floor() as i64, color);
+            } else {
+                self.disc(x, y, width / 2.0, color);
+            }
+        }
+    }
+
+    fn dot(&mut self, x: f64, y: f64, r: f64, color: Rgb) {
+        self.disc(x, y, r, color);
+    }
+
+    fn text(&mut self, x: f64, y: f64, s: &str, anchor: Anchor, title: bool, color: Rgb) {
+        let scale = Self::scale(title);
+        let width = self.text_width(s, title);
+        let x0 = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - width / 2.0,
+            Anchor::End => x - width,
+        }
+        .round() as i64;
+        // Centre the 7-row cap height on `y` (the 8th row is the descender).
+        let y0 = (y - (7 * scale) as f64 / 2.0).round() as i64;
+
+        for (i, ch) in s.chars().enumerate() {
+            let gx = x0 + (i * (GLYPH_W + 1) * scale) as i64;
+            for (col, bits) in glyph(ch).iter().enumerate() {
+                for row in 0..GLYPH_H {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            self.put(
+                                gx + (col * scale + dx) as i64,
+                                y0 + (row * scale + dy) as i64,
+                                color,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn text_width(&self, s: &str, title: bool) -> f64 {
+        let n = s.chars().count();
+        (n * (GLYPH_W + 1) * Self::scale(title)).saturating_sub(Self::scale(title)) as f64
+    }
+}
@@ -0,0 +1,377 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::period::{months_between, shift_period};
+use crate::series::{IndexPoint, PriceIndexSeries};
+
+// ----------------------- Real value math -----------------------
+/// Result of deflating a nominal amount between two price levels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RealValue {
+    pub nominal: f64,
+    pub real: f64,
+    pub loss: f64,
+    pub loss_pct: f64,
+    /// Inflation factor `end_level / start_level`.
+    pub deflator: f64,
+}
+
+/// Express `nominal` (earned at `start_level`) in prices of `end_level`.
+///
+/// Works for CPI index levels (SDMX) as well as for cumulative deflators
+/// built from annual rates (DataMapper), since only the ratio matters:
+///   real = nominal * (start_level / end_level)
+pub fn compute_real_value(nominal: f64, start_level: f64, end_level: f64) -> RealValue {
+    let ratio = start_level / end_level;
+    let real = nominal * ratio;
+
+    RealValue {
+        nominal,
+        real,
+        loss: nominal - real,
+        loss_pct: (1.0 - ratio) * 100.0,
+        deflator: end_level / start_level,
+    }
+}
+
+/// Result of inflating an amount from the start period to later prices
+/// (the reverse question: "what does a 2015 price cost in today's money?").
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct InflatedValue {
+    pub nominal: f64,
+    /// `nominal` expressed in prices of the end period.
+    pub equivalent: f64,
+    pub increase: f64,
+    pub increase_pct: f64,
+    /// Inflation factor `end_level / start_level`.
+    pub deflator: f64,
+}
+
+/// Express `nominal` (priced at `start_level`) in prices of `end_level`:
+///   equivalent = nominal * (end_level / start_level)
+pub fn compute_inflated_value(nominal: f64, start_level: f64, end_level: f64) -> InflatedValue {
+    let factor = end_level / start_level;
+    let equivalent = nominal * factor;
+
+    InflatedValue {
+        nominal,
+        equivalent,
+        increase: equivalent - nominal,
+        increase_pct: (factor - 1.0) * 100.0,
+        deflator: factor,
+    }
+}
+
+/// How far a current nominal amount is from keeping up with inflation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RaiseNeeded {
+    pub current: f64,
+    /// Amount needed now to match the past one in real terms.
+    pub break_even: f64,
+    /// `break_even - current`; negative when already ahead of inflation.
+    pub raise: f64,
+    /// `raise` as a percentage of `current`.
+    pub raise_pct: f64,
+}
+
+/// Raise still required for `current` to reach `break_even`.
+pub fn compute_raise_needed(current: f64, break_even: f64) -> RaiseNeeded {
+    let raise = break_even - current;
+
+    RaiseNeeded {
+        current,
+        break_even,
+        raise,
+        raise_pct: raise / current * 100.0,
+    }
+}
+
+/// An amount of the start period at a target price level: its real value,
+/// its equivalent when inflating, and the raise a current amount still needs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AdjustedAmount {
+    pub result: RealValue,
+    /// `None` unless inflating.
+    pub inflated: Option<InflatedValue>,
+    /// `None` without a current amount.
+    pub raise: Option<RaiseNeeded>,
+}
+
+/// `amount` (at `start_level`) at `target_level`, inflated too when
+/// `inflate`, and compared with `current` when given.
+pub fn adjust_amount(
+    amount: f64,
+    start_level: f64,
+    target_level: f64,
+    inflate: bool,
+    current: Option<f64>,
+) -> AdjustedAmount {
+    let result = compute_real_value(amount, start_level, target_level);
+    AdjustedAmount {
+        result,
+        inflated: inflate.then(|| compute_inflated_value(amount, start_level, target_level)),
+        raise: current.map(|c| compute_raise_needed(c, amount * result.deflator)),
+    }
+}
+
+// ----------------------- Real raise -----------------------
+/// Old vs new pay (or any two amounts at two dates), in real terms.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RealRaise {
+    pub old_amount: f64,
+    pub new_amount: f64,
+    pub nominal_growth_pct: f64,
+    /// Inflation between the two dates, in %.
+    pub inflation_pct: f64,
+    pub real_growth_pct: f64,
+    /// Time between the two dates.
+    pub years: f64,
+    /// Real growth per year, compounded (`None` when both dates are the same).
+    pub annualized_real_growth_pct: Option<f64>,
+    /// `new_amount` minus the old amount inflated to the new date, expressed
+    /// in latest prices: positive is a surplus, negative a shortfall.
+    pub surplus_now: f64,
+}
+
+/// Compare `old_amount` (at `old_level`) with `new_amount` (at `new_level`),
+/// `years` apart, and express the difference at `latest_level`:
+///   real_growth = (new / old) / (level_new / level_old) - 1
+///   surplus_now = (new - old * level_new / level_old) * level_latest / level_new
+pub fn compute_real_raise(
+    old_amount: f64,
+    old_level: f64,
+    new_amount: f64,
+    new_level: f64,
+    latest_level: f64,
+    years: f64,
+) -> RealRaise {
+    let growth = new_amount / old_amount;
+    let inflation = new_level / old_level;
+    let real_growth = growth / inflation;
+
+    RealRaise {
+        old_amount,
+        new_amount,
+        nominal_growth_pct: (growth - 1.0) * 100.0,
+        inflation_pct: (inflation - 1.0) * 100.0,
+        real_growth_pct: (real_growth - 1.0) * 100.0,
+        years,
+        annualized_real_growth_pct: (years > 0.0).then(|| (real_growth.powf(1.0 / years) - 1.0) * 100.0),
+        surplus_now: (new_amount - old_amount * inflation) * latest_level / new_level,
+    }
+}
+
+/// A real raise read from one series.
+#[derive(Debug, Clone, Serialize)]
+pub struct RaiseComparison {
+    /// Price levels of the old and new amounts, at their own dates.
+    pub old: IndexPoint,
+    pub new: IndexPoint,
+    pub latest: IndexPoint,
+    /// Level `surplus_now` is expressed at: the base period, or the latest one
+    /// (priced like the amounts, see [`PriceIndexSeries::amount_point`]).
+    pub target: IndexPoint,
+    pub result: RealRaise,
+}
+
+/// Compare `old` and `new` (`(period, amount)`, in the series' period
+/// format) with the price levels of `series`; the surplus is expressed in
+/// prices of `base`, or of the latest observation.
+pub fn compare_raise(
+    series: &PriceIndexSeries,
+    old: (&str, f64),
+    new: (&str, f64),
+    base: Option<&str>,
+) -> Result<RaiseComparison> {
+    let latest = series.latest().ok_or_else(|| anyhow!("No price data found"))?;
+    let missing = |period: &str| {
+        anyhow!(
+            "No price data for {} (available: {} to {})",
+            period,
+            series.start_period,
+            latest.period
+        )
+    };
+    let old_point = series.amount_point(old.0).ok_or_else(|| missing(old.0))?;
+    let new_point = series.amount_point(new.0).ok_or_else(|| missing(new.0))?;
+    // The latest period is priced like any other amount date (start of the
+    // year for annual deflators).
+    let target = match base {
+        Some(p) => series
+            .amount_point(p)
+            .ok_or_else(|| anyhow!("No price data for base period {}", p))?,
+        None => series.amount_point(&latest.period).ok_or_else(|| missing(&latest.period))?,
+    };
+    let years = months_between(old.0, new.0).unwrap_or_default() as f64 / 12.0;
+
+    Ok(RaiseComparison {
+        result: compute_real_raise(old.1, old_point.level, new.1, new_point.level, target.level, years),
+        old: old_point,
+        new: new_point,
+        latest: latest.clone(),
+        target,
+    })
+}
+
+// ----------------------- Real value over time -----------------------
+/// One period of [`real_value_series`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RealValuePoint {
+    pub period: String,
+    /// CPI index level (SDMX) or cumulative deflator (DataMapper).
+    pub level: f64,
+    /// Change from the previous month, in % (monthly series only).
+    pub mom_pct: Option<f64>,
+    /// Change from the same period one year earlier, in %.
+    pub yoy_pct: Option<f64>,
+    /// Inflation since the start period, in %.
+    pub cumulative_pct: f64,
+    /// `nominal` expressed in this period's prices.
+    pub real: f64,
+    /// Amount needed in this period to match `nominal` at the start (inflate direction).
+    pub equivalent: f64,
+}
+
+/// Every period of `series` with its changes and the real value of `nominal`.
+///
+/// `earlier` may hold levels before the start (e.g. the preceding 12 months)
+/// so the first periods get a year-on-year change too. Changes are only
+/// reported when the earlier period is known, so gaps in the data show up as
+/// `None` rather than as multi-month jumps.
+pub fn real_value_series(nominal: f64, series: &PriceIndexSeries, earlier: &[IndexPoint]) -> Vec<RealValuePoint> {
+    let mut levels: HashMap<&str, f64> = earlier
+        .iter()
+        .chain(&series.points)
+        .map(|p| (p.period.as_str(), p.level))
+        .collect();
+
+    // Annual deflators are end-of-year levels; the base is the end of the year before.
+    let base_period = if series.is_deflator() {
+        shift_period(&series.start_period, -12)
+    } else {
+        None
+    };
+    if let Some(p) = &base_period {
+        levels.insert(p.as_str(), series.base_level);
+    }
+
+    let change = |period: &str, months: i32, level: f64| {
+        let prev = shift_period(period, months)?;
+        levels.get(prev.as_str()).map(|l| (level / l - 1.0) * 100.0)
+    };
+
+    series
+        .points
+        .iter()
+        .map(|p| {
+            let r = compute_real_value(nominal, series.base_level, p.level);
+            RealValuePoint {
+                period: p.period.clone(),
+                level: p.level,
+                mom_pct: if p.period.len() == 7 { change(&p.period, -1, p.level) } else { None },
+                yoy_pct: change(&p.period, -12, p.level),
+                cumulative_pct: (r.deflator - 1.0) * 100.0,
+                real: r.real,
+                equivalent: nominal * r.deflator,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamapper::series_from_yearly;
+    use crate::history::{deflate_history, HistoryRecord};
+    use crate::series::YearInflation;
+
+    #[test]
+    fn adjusted_amount_both_ways_with_raise() {
+        let a = adjust_amount(1000.0, 100.0, 125.0, true, Some(1200.0));
+        assert!((a.result.real - 800.0).abs() < 1e-9);
+        assert!((a.result.loss_pct - 20.0).abs() < 1e-9);
+        assert!((a.inflated.unwrap().equivalent - 1250.0).abs() < 1e-9);
+        let raise = a.raise.unwrap();
+        assert!((raise.break_even - 1250.0).abs() < 1e-9);
+        assert!((raise.raise - 50.0).abs() < 1e-9);
+
+        let a = adjust_amount(1000.0, 100.0, 125.0, false, None);
+        assert!(a.inflated.is_none() && a.raise.is_none());
+    }
+
+    /// Annual deflators from 2019 (or a later start year) to 2024.
+    fn deflators(start_year: i32) -> PriceIndexSeries {
+        let rates = [(2019, 0.6), (2020, -0.1), (2021, 1.9), (2022, 8.7), (2023, 5.9), (2024, 1.1)];
+        let yearly = rates
+            .iter()
+            .filter(|(y, _)| *y >= start_year)
+            .map(|&(year, pct)| YearInflation { year, pct, months: None })
+            .collect();
+        series_from_yearly("ITA", start_year, yearly)
+    }
+
+    #[test]
+    fn raise_between_two_years_does_not_depend_on_the_base() {
+        let plain = compare_raise(&deflators(2021), ("2021", 40000.0), ("2024", 46000.0), None).unwrap();
+        // --base 2019 widens the fetched range to 2019.
+        let based = compare_raise(&deflators(2019), ("2021", 40000.0), ("2024", 46000.0), Some("2019")).unwrap();
+
+        // Both amounts are priced at the start of their year.
+        let inflation = (1.019 * 1.087 * 1.059 - 1.0) * 100.0;
+        for r in [&plain, &based] {
+            assert!((r.result.inflation_pct - inflation).abs() < 1e-9, "{:?}", r.result);
+        }
+        assert!((plain.result.real_growth_pct - based.result.real_growth_pct).abs() < 1e-9);
+        assert_eq!(based.target.period, "2019");
+    }
+
+    #[test]
+    fn latest_year_is_priced_like_the_amounts() {
+        let r = compare_raise(&deflators(2021), ("2021", 40000.0), ("2024", 46000.0), None).unwrap();
+        // 2024 is the latest year: the new amount is already in latest prices.
+        assert_eq!(r.target.period, "2024");
+        assert_eq!(r.target.level, r.new.level);
+        let old_in_2024 = 40000.0 * 1.019 * 1.087 * 1.059;
+        assert!((r.result.surplus_now - (46000.0 - old_in_2024)).abs() < 1e-6);
+    }
+
+    /// Runs a single amount from `start` to `base` through the plain-run
+    /// computation and through `--history`, both with `full` as fetched.
+    fn single_and_history(full: &PriceIndexSeries, start: &str, end: &str, base: &str) {
+        let series = full.slice(start, end).unwrap();
+        let base_point = full.amount_point(base).unwrap();
+        let single = adjust_amount(1000.0, series.base_level, base_point.level, true, None);
+        let history = |date: &str, base: &str| {
+            let records = [HistoryRecord { date: date.to_string(), amount: 1000.0, label: None }];
+            deflate_history(&records, &[date.to_string()], full, Some(base)).unwrap().total_real
+        };
+
+        // Inflating: the start amount in base prices.
+        assert!((single.inflated.unwrap().equivalent - history(start, base)).abs() < 1e-9);
+        // Deflating: the amount at the base period, in start prices.
+        assert!((single.result.real - history(base, start)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn base_period_values_match_the_history_definition() {
+        let point = |period: &str, level: f64| IndexPoint { period: period.to_string(), level };
+        let monthly = PriceIndexSeries {
+            country: "ITA".to_string(),
+            start_period: "2019-06".to_string(),
+            base_level: 103.585,
+            points: vec![
+                point("2019-06", 103.585),
+                point("2020-06", 103.2),
+                point("2021-01", 102.425),
+                point("2025-09", 125.166),
+            ],
+            yearly: Vec::new(),
+            segments: Vec::new(),
+        };
+        single_and_history(&monthly, "2021-01", "2025-09", "2019-06");
+        single_and_history(&monthly, "2019-06", "2025-09", "2021-01");
+        single_and_history(&deflators(2019), "2021", "2024", "2023");
+        single_and_history(&deflators(2019), "2021", "2024", "2019");
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::CacheConfig;
+use crate::datamapper::IMF_DATAMAPPER_BASE;
+use crate::http::HttpConfig;
+use crate::sdmx::{IMF_SDMX_BASE, IMF_SDMX_STRUCTURE_BASE};
+
+// ----------------------- Config file -----------------------
+/// Optional settings read from `config.toml`:
+///
+/// ```toml
+/// sdmx_base = "http://127.0.0.1:8787/sdmx"
+/// sdmx_structure_base = "http://127.0.0.1:8787/structure"
+/// datamapper_base = "http://127.0.0.1:8787/datamapper"
+///
+/// [cache]
+/// policy = "use"              # use | refresh | off | offline
+/// codelist_ttl = "30d"
+/// current_data_ttl = "12h"    # queries that include the current period
+/// historical_data_ttl = "7d"
+///
+/// [http]
+/// connect_timeout = "10s"
+/// read_timeout = "30s"
+/// max_attempts = 4            # including the first request
+/// retry_base_delay = "500ms"  # doubled on every retry (with jitter)
+/// retry_max_delay = "30s"     # also caps a longer Retry-After
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub sdmx_base: Option<String>,
+    pub sdmx_structure_base: Option<String>,
+    pub datamapper_base: Option<String>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+}
+
+/// `~/.config/rip/config.toml` (platform config dir).
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("rip").join("config.toml"))
+}
+
+impl Config {
+    /// Load `path`, or the default config file when `path` is `None`.
+    ///
+    /// A missing default file yields an empty config; a missing explicit file is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (file, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match default_config_path() {
+                Some(p) => (p, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&file) {
+            Ok(t) => t,
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow!(e).context(format!("Cannot read config file {}", file.display()))),
+        };
+
+        toml::from_str(&text).with_context(|| format!("Invalid config file {}", file.display()))
+    }
+}
+
+// ----------------------- API endpoints -----------------------
+/// Base URLs of the IMF APIs (overridable for mirrors and mock servers).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    pub sdmx: String,
+    pub sdmx_structure: String,
+    pub datamapper: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            sdmx: IMF_SDMX_BASE.to_string(),
+            sdmx_structure: IMF_SDMX_STRUCTURE_BASE.to_string(),
+            datamapper: IMF_DATAMAPPER_BASE.to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Replace each base URL for which an override is given (trailing `/` is dropped).
+    pub fn with_overrides(mut self, sdmx: Option<&str>, sdmx_structure: Option<&str>, datamapper: Option<&str>) -> Self {
+        fn set(slot: &mut String, value: Option<&str>) {
+            if let Some(v) = value.map(str::trim).filter(|v| !v.is_empty()) {
+                *slot = v.trim_end_matches('/').to_string();
+            }
+        }
+
+        set(&mut self.sdmx, sdmx);
+        set(&mut self.sdmx_structure, sdmx_structure);
+        set(&mut self.datamapper, datamapper);
+        self
+    }
+
+    /// Overrides from a config file.
+    pub fn with_config(self, config: &Config) -> Self {
+        self.with_overrides(
+            config.sdmx_base.as_deref(),
+            config.sdmx_structure_base.as_deref(),
+            config.datamapper_base.as_deref(),
+        )
+    }
+
+    /// Endpoints served by `rip serve-fixtures` at `http://{addr}`.
+    pub fn fixture_server(addr: &str) -> Self {
+        Self {
+            sdmx: format!("http://{}/sdmx", addr),
+            sdmx_structure: format!("http://{}/structure", addr),
+            datamapper: format!("http://{}/datamapper", addr),
+        }
+    }
+}
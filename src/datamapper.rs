@@ -0,0 +1,491 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chrono::Datelike;
+use reqwest::header::{ACCEPT, ACCEPT_LANGUAGE, REFERER};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cache::{Cache, Resource};
+use crate::config::Endpoints;
+use crate::http::{HttpClient, HttpSettings, Retrieval};
+use crate::period::PeriodRange;
+use crate::provider::CpiProvider;
+use crate::series::{IndexPoint, Item, PriceIndexSeries, YearInflation};
+use crate::store::{SeriesStore, StoredSeries};
+
+// ----------------------- Constants -----------------------
+pub const IMF_DATAMAPPER_BASE: &str = "https://www.imf.org/external/datamapper/api/v1";
+
+// DataMapper fixed indicator for annual inflation rate
+pub const DATAMAPPER_INDICATOR: &str = "PCPIPCH"; // annual inflation (%), avg consumer prices
+
+// ----------------------- Year-boundary convention -----------------------
+/// Which part of the first and last years' inflation the deflator applies.
+/// Annual rates carry no intra-year levels, so what "2024 → 2024" means is a
+/// matter of convention.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum YearConvention {
+    /// Start of the first year to the end of the last one: a same-year
+    /// comparison applies that year's full inflation.
+    #[default]
+    StartInclusive,
+    /// End of the first year to the end of the last one: the first year's
+    /// inflation is skipped, so a same-year comparison gives zero.
+    StartExclusive,
+    /// Ratio of the last and first years' average price levels, each
+    /// averaged over its year with prices growing steadily by that year's
+    /// rate (see [`average_level_series`]); zero within the same year.
+    AveragePriceLevel,
+}
+
+impl YearConvention {
+    /// Months of `year` applied for a deflator starting in `first` (whole
+    /// years for average-price-level, whose levels are averaged instead).
+    pub fn months(self, year: i32, first: i32) -> u32 {
+        match self {
+            Self::StartExclusive if year == first => 0,
+            Self::StartInclusive | Self::StartExclusive | Self::AveragePriceLevel => 12,
+        }
+    }
+}
+
+impl FromStr for YearConvention {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "start-inclusive" | "inclusive" => Ok(Self::StartInclusive),
+            "start-exclusive" | "exclusive" => Ok(Self::StartExclusive),
+            "average-price-level" | "average" => Ok(Self::AveragePriceLevel),
+            _ => Err(anyhow!(
+                "Unknown year convention '{}' (expected start-inclusive, start-exclusive or average-price-level)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for YearConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::StartInclusive => "start-inclusive",
+            Self::StartExclusive => "start-exclusive",
+            Self::AveragePriceLevel => "average-price-level",
+        };
+        f.write_str(s)
+    }
+}
+
+// ----------------------- DataMapper: anti-403 client -----------------------
+pub fn build_client(settings: HttpSettings) -> Result<HttpClient> {
+    let builder = Client::builder()
+        .http1_only()
+        .cookie_store(true)
+        .user_agent("curl/8.5.0")
+        .default_headers({
+            let mut h = reqwest::header::HeaderMap::new();
+            h.insert(ACCEPT, "application/json,text/plain,*/*".parse().unwrap());
+            h.insert(ACCEPT_LANGUAGE, "en-US,en;q=0.9".parse().unwrap());
+            h.insert(REFERER, "https://www.imf.org/external/datamapper/".parse().unwrap());
+            h
+        });
+    HttpClient::new(builder, settings).context("Failed to build DataMapper HTTP client")
+}
+
+// ----------------------- Provider -----------------------
+/// Annual inflation rates (PCPIPCH) from the IMF DataMapper API, chained
+/// into a cumulative deflator.
+pub struct DataMapperProvider {
+    client: HttpClient,
+    cache: Cache,
+    base: String,
+    prorate: bool,
+    convention: YearConvention,
+}
+
+impl DataMapperProvider {
+    pub fn new(client: HttpClient, cache: Cache) -> Self {
+        Self {
+            client,
+            cache,
+            base: IMF_DATAMAPPER_BASE.to_string(),
+            prorate: false,
+            convention: YearConvention::default(),
+        }
+    }
+
+    /// Apply only the months of the first and last years covered by the
+    /// requested range (see [`prorate_yearly`]).
+    pub fn with_prorating(mut self, prorate: bool) -> Self {
+        self.prorate = prorate;
+        self
+    }
+
+    /// Year-boundary convention for ranges without pro-rating.
+    pub fn with_convention(mut self, convention: YearConvention) -> Self {
+        self.convention = convention;
+        self
+    }
+
+    /// Use the DataMapper base URL from `endpoints`.
+    pub fn with_endpoints(mut self, endpoints: &Endpoints) -> Self {
+        self.base = endpoints.datamapper.clone();
+        self
+    }
+}
+
+#[async_trait]
+impl CpiProvider for DataMapperProvider {
+    fn source_label(&self) -> &'static str {
+        "IMF DataMapper"
+    }
+
+    fn indicator(&self) -> &'static str {
+        DATAMAPPER_INDICATOR
+    }
+
+    async fn list_countries(&self) -> Result<Vec<Item>> {
+        self.load_or_fetch_countries().await
+    }
+
+    async fn fetch_series(&self, country: &str, range: &PeriodRange) -> Result<PriceIndexSeries> {
+        let start_year = range.start_year();
+        let mut yearly = self
+            .fetch_yearly_pcpipch(country, start_year, range.end_year())
+            .await?;
+        if self.prorate {
+            prorate_yearly(&mut yearly, range);
+        } else if self.convention == YearConvention::AveragePriceLevel {
+            return Ok(average_level_series(country, start_year, yearly));
+        } else {
+            apply_convention(&mut yearly, start_year, self.convention);
+        }
+
+        Ok(series_from_yearly(country, start_year, yearly))
+    }
+
+    fn retrievals(&self) -> Vec<Retrieval> {
+        self.client.retrievals()
+    }
+}
+
+/// Mark the first and last years of `range` with the months they cover, from
+/// the months of `range.start` and `range.end` (e.g. a November start keeps
+/// 2 months of the first year). Whole years are left as they are.
+pub fn prorate_yearly(yearly: &mut [YearInflation], range: &PeriodRange) {
+    let month = |ym: &str| ym.get(5..7).and_then(|m| m.parse::<u32>().ok()).unwrap_or(1);
+    let (first, last) = (range.start_year(), range.end_year());
+    for yi in yearly.iter_mut() {
+        let mut months = 12;
+        if yi.year == first {
+            months -= month(&range.start) - 1;
+        }
+        if yi.year == last {
+            months -= 12 - month(&range.end);
+        }
+        yi.months = (months < 12).then_some(months);
+    }
+}
+
+/// Mark the first year with the months `convention` applies.
+pub fn apply_convention(yearly: &mut [YearInflation], first: i32, convention: YearConvention) {
+    for yi in yearly.iter_mut() {
+        let months = convention.months(yi.year, first);
+        yi.months = (months < 12).then_some(months);
+    }
+}
+
+/// Chain annual rates into cumulative deflator levels (base 1.0 at the start
+/// of `start_year`):
+///   deflator_y = Π_{start..=y} (1 + PCPIPCH / 100)
+///
+/// Pro-rated years compound geometrically: `(1 + PCPIPCH / 100)^(months / 12)`.
+pub fn series_from_yearly(country: &str, start_year: i32, yearly: Vec<YearInflation>) -> PriceIndexSeries {
+    let mut level = 1.0_f64;
+    let points = yearly
+        .iter()
+        .map(|yi| {
+            let share = yi.months.map_or(1.0, |m| m as f64 / 12.0);
+            level *= (1.0 + (yi.pct / 100.0)).powf(share);
+            IndexPoint { period: yi.year.to_string(), level }
+        })
+        .collect();
+
+    PriceIndexSeries {
+        country: country.to_string(),
+        start_period: start_year.to_string(),
+        base_level: 1.0,
+        points,
+        yearly,
+        segments: Vec::new(),
+    }
+}
+
+/// Annual average price levels relative to the first year's (base 1.0):
+///   avg_y = L_{y-1} * (g_y - 1) / ln g_y,   g_y = 1 + PCPIPCH_y / 100
+/// where `L_{y-1}` is the end-of-year level chained as in
+/// [`series_from_yearly`] and prices grow steadily within each year, so
+/// `(g - 1) / ln g = ∫₀¹ g^t dt` is the year's mean relative to its start
+/// (about `√g`, the mid-year level).
+pub fn average_level_series(country: &str, start_year: i32, yearly: Vec<YearInflation>) -> PriceIndexSeries {
+    let mean_over_year = |pct: f64| {
+        let g = 1.0 + pct / 100.0;
+        if pct.abs() < 1e-9 {
+            1.0
+        } else {
+            (g - 1.0) / g.ln()
+        }
+    };
+    let first = yearly.first().map_or(1.0, |yi| mean_over_year(yi.pct));
+
+    let mut year_start = 1.0_f64;
+    let points = yearly
+        .iter()
+        .map(|yi| {
+            let level = year_start * mean_over_year(yi.pct) / first;
+            year_start *= 1.0 + yi.pct / 100.0;
+            IndexPoint { period: yi.year.to_string(), level }
+        })
+        .collect();
+
+    PriceIndexSeries {
+        country: country.to_string(),
+        start_period: start_year.to_string(),
+        base_level: 1.0,
+        points,
+        yearly,
+        segments: Vec::new(),
+    }
+}
+
+// ----------------------- DataMapper: fetching (cache + HTTP) -----------------------
+impl DataMapperProvider {
+    // ---- list countries ----
+    pub async fn load_or_fetch_countries(&self) -> Result<Vec<Item>> {
+        let cache_name = "dm_countries.json";
+
+        if let Some(b) = self.cache.read(cache_name, Resource::Codelist) {
+            match serde_json::from_slice::<Vec<Item>>(&b) {
+                Ok(v) if !v.is_empty() => {
+                    if let Some(meta) = self.cache.meta(cache_name) {
+                        self.client.record_cached(&meta.url, meta.fetched_at);
+                    }
+                    return Ok(v);
+                }
+                _ => self.cache.evict(cache_name),
+            }
+        }
+        self.cache.ensure_online("the DataMapper country list")?;
+
+        let url = format!("{}/countries", self.base);
+        let resp = self
+            .client
+            .get(&url)
+            .await
+            .context("HTTP error fetching DataMapper countries")?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            return Err(anyhow!("DataMapper countries returned {}.\nBody:\n{}", status, body));
+        }
+
+        let json: Value = resp.json().await.context("Invalid JSON from DataMapper countries")?;
+        let obj = json.get("countries").cloned().unwrap_or(json);
+        let map = obj
+            .as_object()
+            .ok_or_else(|| anyhow!("Unexpected DataMapper countries JSON shape"))?;
+
+        let mut out = Vec::with_capacity(map.len());
+        for (code, info) in map {
+            let name = info
+                .get("label")
+                .and_then(|x| x.as_str())
+                .unwrap_or(code)
+                .to_string();
+            out.push(Item { code: code.to_string(), name });
+        }
+
+        out.sort_by_key(|x| x.name.to_lowercase());
+
+        if !out.is_empty() {
+            let _ = self.cache.write(cache_name, &serde_json::to_vec_pretty(&out)?, &url);
+        }
+
+        Ok(out)
+    }
+
+    // ---- fetch PCPIPCH per year (series store) ----
+    /// Annual rates for `start_year..=end_year`, sliced from the locally stored
+    /// full PCPIPCH series of the country (refetched whole once its TTL expires;
+    /// the API has no incremental queries and the series is small).
+    pub async fn fetch_yearly_pcpipch(
+        &self,
+        country_iso3: &str,
+        start_year: i32,
+        end_year: i32,
+    ) -> Result<Vec<YearInflation>> {
+        let yearly = self.fetch_pcpipch_rates(country_iso3, start_year, end_year).await?;
+        if yearly.is_empty() {
+            return Err(anyhow!(
+                "No {} observations for {} in {}–{}",
+                DATAMAPPER_INDICATOR,
+                country_iso3,
+                start_year,
+                end_year
+            ));
+        }
+        Ok(yearly)
+    }
+
+    /// Like [`Self::fetch_yearly_pcpipch`], but "no data" (a 404, or no values
+    /// for the country or the years) is an empty Vec rather than an error.
+    /// Failed requests and unparsable bodies are still errors.
+    pub async fn fetch_pcpipch_rates(
+        &self,
+        country_iso3: &str,
+        start_year: i32,
+        end_year: i32,
+    ) -> Result<Vec<YearInflation>> {
+        let store = SeriesStore::new(&self.cache);
+        let source_url = format!("{}/{}/{}", self.base, DATAMAPPER_INDICATOR, country_iso3);
+        let stored = store.load("datamapper", DATAMAPPER_INDICATOR, country_iso3);
+
+        let current_year = chrono::Utc::now().date_naive().year();
+        let resource = Resource::data(&end_year.to_string(), &current_year.to_string());
+        let plan = store.plan_whole(stored.as_ref(), resource);
+        if stored.is_none() && plan.is_empty() {
+            self.cache
+                .ensure_online(&format!("DataMapper {} for {}", DATAMAPPER_INDICATOR, country_iso3))?;
+        }
+
+        if let Some(s) = stored.as_ref().filter(|_| plan.is_empty()) {
+            self.client.record_cached(&s.source_url, s.fetched_at);
+        }
+
+        let mut series = stored
+            .unwrap_or_else(|| StoredSeries::new("datamapper", DATAMAPPER_INDICATOR, country_iso3, &source_url));
+        if !plan.is_empty() {
+            let resp = self
+                .client
+                .get(&source_url)
+                .await
+                .context("HTTP error fetching DataMapper PCPIPCH values")?;
+
+            // A 404 means DataMapper has no series for the country.
+            if resp.status() != StatusCode::NOT_FOUND {
+                if !resp.status().is_success() {
+                    let status = resp.status();
+                    let body = resp.text().await.unwrap_or_default();
+                    return Err(anyhow!("DataMapper values returned {}.\nBody:\n{}", status, body));
+                }
+
+                let b = resp.bytes().await?;
+                let observations = parse_values(&b, country_iso3)?;
+                if !observations.is_empty() {
+                    series.merge(observations, None, true);
+                    store.save(&series)?;
+                }
+            }
+        }
+
+        let yearly: Vec<YearInflation> = series
+            .slice(&start_year.to_string(), &end_year.to_string())
+            .into_iter()
+            .filter_map(|(y, pct)| y.parse().ok().map(|year| YearInflation { year, pct, months: None }))
+            .collect();
+
+        if yearly.is_empty() {
+            self.cache.ensure_online(&format!(
+                "DataMapper {} for {} in {}–{}",
+                DATAMAPPER_INDICATOR, country_iso3, start_year, end_year
+            ))?;
+        }
+        Ok(yearly)
+    }
+}
+
+/// Extract every numeric `values.PCPIPCH.<country>.<year>` as `(year, pct)`;
+/// empty when the response has no values for the country.
+pub fn parse_values(json_bytes: &[u8], country_iso3: &str) -> Result<Vec<(String, f64)>> {
+    let json: Value = serde_json::from_slice(json_bytes).context("Invalid JSON from DataMapper values")?;
+
+    let values = json
+        .get("values")
+        .ok_or_else(|| anyhow!("Unexpected DataMapper response (missing 'values')"))?;
+
+    let Some(series) = values
+        .get(DATAMAPPER_INDICATOR)
+        .and_then(|v| v.get(country_iso3))
+        .and_then(|v| v.as_object())
+    else {
+        return Ok(Vec::new());
+    };
+
+    Ok(series
+        .iter()
+        .filter_map(|(y, v)| v.as_f64().map(|pct| (y.clone(), pct)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: [(i32, f64); 3] = [(2022, 8.2), (2023, 5.9), (2024, 1.1)];
+
+    fn deflator(first: i32, last: i32, convention: YearConvention) -> f64 {
+        let mut yearly: Vec<YearInflation> = RATES
+            .iter()
+            .filter(|(year, _)| (first..=last).contains(year))
+            .map(|&(year, pct)| YearInflation { year, pct, months: None })
+            .collect();
+        if convention == YearConvention::AveragePriceLevel {
+            return average_level_series("ITA", first, yearly).deflator().unwrap();
+        }
+        apply_convention(&mut yearly, first, convention);
+        series_from_yearly("ITA", first, yearly).deflator().unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-12, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn start_inclusive_applies_every_year() {
+        assert_close(deflator(2024, 2024, YearConvention::StartInclusive), 1.011);
+        assert_close(deflator(2023, 2024, YearConvention::StartInclusive), 1.059 * 1.011);
+        assert_close(deflator(2022, 2024, YearConvention::StartInclusive), 1.082 * 1.059 * 1.011);
+    }
+
+    #[test]
+    fn start_exclusive_skips_the_first_year() {
+        assert_close(deflator(2024, 2024, YearConvention::StartExclusive), 1.0);
+        assert_close(deflator(2023, 2024, YearConvention::StartExclusive), 1.011);
+        assert_close(deflator(2022, 2024, YearConvention::StartExclusive), 1.059 * 1.011);
+    }
+
+    #[test]
+    fn average_price_level_is_the_ratio_of_annual_averages() {
+        // Mean of each year's level by the midpoint rule, with prices growing
+        // steadily through the year from 100 at the start of 2022.
+        let avg = |year: i32| {
+            let start = 100.0 * RATES.iter().filter(|(y, _)| *y < year).map(|(_, pct)| 1.0 + pct / 100.0).product::<f64>();
+            let g = 1.0 + RATES.iter().find(|(y, _)| *y == year).unwrap().1 / 100.0;
+            let steps = 20_000;
+            (0..steps).map(|i| start * g.powf((i as f64 + 0.5) / steps as f64)).sum::<f64>() / steps as f64
+        };
+        let close = |actual: f64, expected: f64| assert!((actual - expected).abs() < 1e-8, "{actual} != {expected}");
+
+        close(deflator(2024, 2024, YearConvention::AveragePriceLevel), 1.0);
+        close(deflator(2023, 2024, YearConvention::AveragePriceLevel), avg(2024) / avg(2023));
+        close(deflator(2022, 2024, YearConvention::AveragePriceLevel), avg(2024) / avg(2022));
+
+        // Between the two year-end conventions, not equal to either.
+        let adjacent = deflator(2023, 2024, YearConvention::AveragePriceLevel);
+        assert!(adjacent > deflator(2023, 2024, YearConvention::StartExclusive) + 1e-3);
+        assert!(adjacent < deflator(2023, 2024, YearConvention::StartInclusive) - 1e-3);
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::period::parse_year_loose;
+use crate::sdmx::SDMX_CPI_WEIGHTS_TRANSFORMATION;
+use crate::series::{IndexPoint, Item, PriceIndexSeries};
+
+// ----------------------- Weights -----------------------
+/// Weight in force for `year`: the latest observation up to that year, or
+/// the earliest one when the series starts later.
+pub fn weight_for_year(weights: &[(String, f64)], year: i32) -> Option<(String, f64)> {
+    let year = format!("{:04}", year);
+    weights
+        .iter()
+        .rev()
+        .find(|(y, _)| y.as_str() <= year.as_str())
+        .or_else(|| weights.first())
+        .cloned()
+}
+
+// ----------------------- Decomposition -----------------------
+/// Price levels and official weight of one COICOP division over the range.
+#[derive(Debug, Clone)]
+pub struct DivisionInput {
+    pub coicop: Item,
+    pub start_level: f64,
+    pub end_level: f64,
+    /// Year the weight comes from, and its value (same unit as the total weight).
+    pub weight_year: String,
+    pub weight: f64,
+}
+
+/// Percentage-point contribution of one division to headline inflation.
+#[derive(Debug, Clone, Serialize)]
+pub struct Contribution {
+    pub coicop: Item,
+    pub weight_year: String,
+    /// Share of the division in the basket (`weight / total weight`).
+    pub share: f64,
+    /// Inflation of the division over the range (%).
+    pub inflation_pct: f64,
+    /// `share * inflation_pct`, in percentage points of headline inflation.
+    pub contribution_pp: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Decomposition {
+    /// Headline (all-items) inflation over the range (%).
+    pub headline_pct: f64,
+    /// Largest contribution first.
+    pub contributions: Vec<Contribution>,
+    /// Sum of the contributions (pp).
+    pub explained_pp: f64,
+    /// `headline_pct - explained_pp`: divisions without data, weight updates
+    /// within the range, rounding.
+    pub residual_pp: f64,
+}
+
+/// Split headline inflation `headline_start → headline_end` into division
+/// contributions, each weighted by its share of `total_weight` (the all-items
+/// weight, or the sum of the division weights when none is published).
+///
+/// Exact for a fixed-weight index whose weights are expenditure shares at the
+/// start of the range; the residual shows how far the official index is from
+/// that. Published weights are not price-updated to the start of the range,
+/// so relative price changes since their reference period land in the residual.
+pub fn decompose(headline_start: f64, headline_end: f64, divisions: &[DivisionInput], total_weight: f64) -> Decomposition {
+    let headline_pct = (headline_end / headline_start - 1.0) * 100.0;
+    let mut contributions: Vec<Contribution> = divisions
+        .iter()
+        .map(|d| {
+            let share = d.weight / total_weight;
+            let inflation_pct = (d.end_level / d.start_level - 1.0) * 100.0;
+            Contribution {
+                coicop: d.coicop.clone(),
+                weight_year: d.weight_year.clone(),
+                share,
+                inflation_pct,
+                contribution_pp: share * inflation_pct,
+            }
+        })
+        .collect();
+    contributions.sort_by(|a, b| b.contribution_pp.total_cmp(&a.contribution_pp));
+
+    let explained_pp = contributions.iter().map(|c| c.contribution_pp).sum();
+    Decomposition {
+        headline_pct,
+        contributions,
+        explained_pp,
+        residual_pp: headline_pct - explained_pp,
+    }
+}
+
+// ----------------------- Headline decomposition -----------------------
+/// What was found for one COICOP division.
+#[derive(Debug, Clone)]
+pub struct DivisionData {
+    pub coicop: Item,
+    /// Division index over the headline range (`None`: not published).
+    pub series: Option<PriceIndexSeries>,
+    /// Official weights by year (empty: not published).
+    pub weights: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeadlineDecomposition {
+    /// All-items CPI at both ends of the range.
+    pub start: IndexPoint,
+    pub latest: IndexPoint,
+    pub result: Decomposition,
+    /// Divisions left out (no index or weight for the range).
+    pub skipped: Vec<Item>,
+}
+
+/// Decompose `headline` inflation from its first to its latest period into
+/// the `divisions`' contributions, with the weights in force at the start
+/// (`all_items_weights` give the total; the divisions' sum when empty).
+pub fn decompose_headline(
+    headline: &PriceIndexSeries,
+    all_items_weights: &[(String, f64)],
+    divisions: Vec<DivisionData>,
+) -> Result<HeadlineDecomposition> {
+    let latest = headline.latest().ok_or_else(|| anyhow!("No CPI data found"))?.clone();
+    let start = IndexPoint { period: headline.start_period.clone(), level: headline.base_level };
+    let weight_year = parse_year_loose(&start.period)?;
+
+    let mut inputs = Vec::with_capacity(divisions.len());
+    let mut skipped = Vec::new();
+    for d in divisions {
+        let levels = d
+            .series
+            .as_ref()
+            .and_then(|s| s.level_at(&start.period).zip(s.level_at(&latest.period)));
+        match (levels, weight_for_year(&d.weights, weight_year)) {
+            (Some((start_level, end_level)), Some((year, weight))) => inputs.push(DivisionInput {
+                coicop: d.coicop,
+                start_level,
+                end_level,
+                weight_year: year,
+                weight,
+            }),
+            _ => skipped.push(d.coicop),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(anyhow!(
+            "No COICOP division of {} has both an index and a weight over {} → {} (no division indices or {} series published?)",
+            headline.country,
+            start.period,
+            latest.period,
+            SDMX_CPI_WEIGHTS_TRANSFORMATION
+        ));
+    }
+
+    // Shares of the all-items weight; of the divisions found when it is not published.
+    let total_weight = weight_for_year(all_items_weights, weight_year)
+        .map_or_else(|| inputs.iter().map(|d| d.weight).sum(), |(_, w)| w);
+    Ok(HeadlineDecomposition {
+        result: decompose(start.level, latest.level, &inputs, total_weight),
+        start,
+        latest,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(start: f64, end: f64) -> PriceIndexSeries {
+        PriceIndexSeries {
+            country: "ITA".to_string(),
+            start_period: "2023-01".to_string(),
+            base_level: start,
+            points: vec![
+                IndexPoint { period: "2023-01".to_string(), level: start },
+                IndexPoint { period: "2024-01".to_string(), level: end },
+            ],
+            yearly: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    fn division(code: &str, series: Option<PriceIndexSeries>, weight: Option<f64>) -> DivisionData {
+        DivisionData {
+            coicop: Item { code: code.to_string(), name: code.to_string() },
+            series,
+            weights: weight.map(|w| ("2023".to_string(), w)).into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn divisions_without_an_index_or_a_weight_are_skipped() {
+        let divisions = vec![
+            division("CP01", Some(series(100.0, 110.0)), Some(400.0)),
+            division("CP02", None, Some(100.0)),
+            division("CP03", Some(series(100.0, 120.0)), None),
+            division("CP04", Some(series(100.0, 102.0)), Some(600.0)),
+        ];
+        let weights = [("2023".to_string(), 1000.0)];
+        let d = decompose_headline(&series(100.0, 105.0), &weights, divisions).unwrap();
+
+        let skipped: Vec<_> = d.skipped.iter().map(|i| i.code.as_str()).collect();
+        assert_eq!(skipped, ["CP02", "CP03"]);
+        let contributions: Vec<_> = d.result.contributions.iter().map(|c| c.coicop.code.as_str()).collect();
+        assert_eq!(contributions, ["CP01", "CP04"]);
+        // 0.4 × 10% + 0.6 × 2% = 5.2 pp of the 5% headline.
+        assert!((d.result.explained_pp - 5.2).abs() < 1e-9);
+        assert!((d.result.residual_pp + 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_usable_division_is_an_error() {
+        let divisions = vec![division("CP01", None, Some(400.0))];
+        assert!(decompose_headline(&series(100.0, 105.0), &[], divisions).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// ----------------------- Built-in fixtures -----------------------
+// Recorded IMF responses, laid out like the URL paths they answer:
+//   /sdmx/data/CPI/{key}                          -> sdmx/data/CPI/{key}.xml
+//   /structure/codelist/IMF/{id}/latest           -> structure/codelist/IMF/{id}/latest.xml
+//   /datamapper/countries, /datamapper/PCPIPCH/X  -> datamapper/....json
+const BUILTIN: &[(&str, &[u8])] = &[
+    (
+        "sdmx/data/CPI/USA.CPI._T.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI._T.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI._T.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI._T.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP01.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP01.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP04.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP04.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP07.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP07.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP01.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP01.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP04.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP04.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP07.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP07.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI._T.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI._T.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP01.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP01.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP04.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP04.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/USA.CPI.CP07.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI.CP07.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI._T.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI._T.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP01.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP01.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP04.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP04.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI.CP07.WGT.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI.CP07.WGT.A.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.HICP._T.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.HICP._T.IX.M.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI._T.IX.Q.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI._T.IX.Q.xml"),
+    ),
+    (
+        "sdmx/data/CPI/ITA.CPI._T.IX.A.xml",
+        include_bytes!("../fixtures/sdmx/data/CPI/ITA.CPI._T.IX.A.xml"),
+    ),
+    (
+        "sdmx/data/PPI/ITA.PPI.IX.M.xml",
+        include_bytes!("../fixtures/sdmx/data/PPI/ITA.PPI.IX.M.xml"),
+    ),
+    (
+        "structure/codelist/IMF/CL_COUNTRY_ISO3/latest.xml",
+        include_bytes!("../fixtures/structure/codelist/IMF/CL_COUNTRY_ISO3/latest.xml"),
+    ),
+    (
+        "structure/codelist/IMF/CL_COICOP_1999/latest.xml",
+        include_bytes!("../fixtures/structure/codelist/IMF/CL_COICOP_1999/latest.xml"),
+    ),
+    (
+        "structure/dataflow/IMF/PPI/latest.xml",
+        include_bytes!("../fixtures/structure/dataflow/IMF/PPI/latest.xml"),
+    ),
+    ("datamapper/countries.json", include_bytes!("../fixtures/datamapper/countries.json")),
+    ("datamapper/PCPIPCH/USA.json", include_bytes!("../fixtures/datamapper/PCPIPCH/USA.json")),
+    ("datamapper/PCPIPCH/ITA.json", include_bytes!("../fixtures/datamapper/PCPIPCH/ITA.json")),
+];
+
+/// Relative fixture file for a request path (query string already stripped).
+fn fixture_file(path: &str) -> Option<String> {
+    let rel = path.trim_start_matches('/').trim_end_matches('/');
+    if rel.is_empty() || rel.split('/').any(|seg| seg.is_empty() || seg == "." || seg == "..") {
+        return None;
+    }
+
+    let ext = match rel.split('/').next()? {
+        "sdmx" | "structure" => "xml",
+        "datamapper" => "json",
+        _ => return None,
+    };
+    Some(format!("{}.{}", rel, ext))
+}
+
+// ----------------------- Fault injection -----------------------
+/// Deliberate misbehaviour, to exercise client timeouts and retries.
+///
+/// The first `times` requests to each path (every request if `times` is 0)
+/// are delayed by `delay` and/or answered with `status` instead of the fixture.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    pub status: Option<u16>,
+    pub times: u32,
+    /// `Retry-After` seconds sent with a failure status.
+    pub retry_after: Option<u64>,
+    pub delay: Option<Duration>,
+}
+
+impl Faults {
+    pub fn is_active(&self) -> bool {
+        self.status.is_some() || self.delay.is_some()
+    }
+}
+
+// ----------------------- Fixture server -----------------------
+/// Minimal HTTP server answering SDMX and DataMapper requests from recorded
+/// files, for tests and air-gapped machines.
+///
+/// Files in `dir` (same layout as the built-in set) take precedence over the
+/// built-in fixtures. Point RIP at it with [`crate::config::Endpoints::fixture_server`].
+#[derive(Debug, Clone, Default)]
+pub struct FixtureServer {
+    dir: Option<PathBuf>,
+    faults: Faults,
+    /// Log every request and its status to stderr.
+    verbose: bool,
+    /// Requests seen per path (for `Faults::times`); shared by clones.
+    hits: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl FixtureServer {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            ..Self::default()
+        }
+    }
+
+    /// Misbehave on purpose (see [`Faults`]).
+    pub fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Log every request and the status it got to stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Requests received so far for `path` (query string excluded), by this
+    /// server or any clone of it.
+    pub fn hits(&self, path: &str) -> u32 {
+        let hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        hits.get(path).copied().unwrap_or_default()
+    }
+
+    /// Whether this request to `path` should be faulty; counts the hit.
+    fn faulty(&self, path: &str) -> bool {
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+        let n = hits.entry(path.to_string()).or_default();
+        *n += 1;
+        self.faults.is_active() && (self.faults.times == 0 || *n <= self.faults.times)
+    }
+
+    /// Body and content type for a request path, if a fixture exists.
+    pub fn lookup(&self, path: &str) -> Option<(Vec<u8>, &'static str)> {
+        let rel = fixture_file(path)?;
+        let content_type = if rel.ends_with(".json") {
+            "application/json"
+        } else {
+            "application/xml"
+        };
+
+        if let Some(dir) = &self.dir {
+            if let Ok(b) = fs::read(dir.join(Path::new(&rel))) {
+                return Some((b, content_type));
+            }
+        }
+
+        BUILTIN
+            .iter()
+            .find(|(name, _)| *name == rel)
+            .map(|(_, b)| (b.to_vec(), content_type))
+    }
+
+    /// Accept connections until the task is dropped.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let server = Arc::new(self);
+        loop {
+            let (stream, _) = listener.accept().await.context("Fixture server accept failed")?;
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                let _ = server.handle(stream).await;
+            });
+        }
+    }
+
+    async fn handle(&self, mut stream: TcpStream) -> Result<()> {
+        let mut buf = Vec::with_capacity(1024);
+        let mut chunk = [0u8; 1024];
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < 16 * 1024 {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        // "GET /sdmx/data/CPI/USA.CPI._T.IX.M?startPeriod=... HTTP/1.1"
+        let head = String::from_utf8_lossy(&buf);
+        let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+        let method = parts.next().unwrap_or_default();
+        let target = parts.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default();
+
+        let faulty = self.faulty(path);
+        if faulty {
+            if let Some(d) = self.faults.delay {
+                tokio::time::sleep(d).await;
+            }
+        }
+
+        let mut extra = String::new();
+        let injected = self.faults.status.filter(|_| faulty);
+        let (status, body, content_type) = match (method, self.lookup(path), injected) {
+            (_, _, Some(code)) => {
+                if let Some(secs) = self.faults.retry_after {
+                    extra = format!("Retry-After: {}\r\n", secs);
+                }
+                let status = format!("{} Injected Fault", code);
+                (status, format!("Injected {} for {}\n", code, path).into_bytes(), "text/plain")
+            }
+            ("GET", Some((b, ct)), None) => ("200 OK".to_string(), b, ct),
+            ("GET", None, None) => (
+                "404 Not Found".to_string(),
+                format!("No fixture for {}\n", path).into_bytes(),
+                "text/plain",
+            ),
+            _ => (
+                "405 Method Not Allowed".to_string(),
+                b"Only GET is supported\n".to_vec(),
+                "text/plain",
+            ),
+        };
+        if self.verbose {
+            eprintln!("{} {} -> {}", method, target, status);
+        }
+
+        let header = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len(),
+            extra
+        );
+        stream.write_all(header.as_bytes()).await?;
+        stream.write_all(&body).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
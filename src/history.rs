@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::period::{parse_year_loose, parse_ym, Frequency};
+use crate::series::{IndexPoint, PriceIndexSeries};
+
+// ----------------------- Records -----------------------
+/// One dated payment of a salary history file (`--history`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// "YYYY-MM", "YYYY-MM-DD" or "YYYY".
+    pub date: String,
+    pub amount: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Read records from a `.csv` or `.json` file (chosen by extension).
+///
+/// CSV: `date,amount[,label]` rows, with an optional header line.
+/// JSON: an array of `{"date": ..., "amount": ..., "label": ...}` objects.
+pub fn read_history(path: &Path) -> Result<Vec<HistoryRecord>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let records = match ext.as_str() {
+        "csv" => parse_history_csv(&text),
+        "json" => serde_json::from_str(&text).map_err(anyhow::Error::from),
+        _ => return Err(anyhow!("Unsupported history file '{}' (use .csv or .json)", path.display())),
+    }
+    .with_context(|| format!("Invalid history file {}", path.display()))?;
+
+    if records.is_empty() {
+        return Err(anyhow!("No records in {}", path.display()));
+    }
+    Ok(records)
+}
+
+pub fn parse_history_csv(text: &str) -> Result<Vec<HistoryRecord>> {
+    let mut records = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line);
+        let (date, amount) = match (fields.first(), fields.get(1)) {
+            (Some(d), Some(a)) => (d.trim(), a.trim()),
+            _ => return Err(anyhow!("Line {}: expected date,amount[,label]", i + 1)),
+        };
+        let amount: f64 = match amount.parse() {
+            Ok(a) => a,
+            // Header line.
+            Err(_) if records.is_empty() && date.eq_ignore_ascii_case("date") => continue,
+            Err(_) => return Err(anyhow!("Line {}: invalid amount '{}'", i + 1, amount)),
+        };
+        let label = fields.get(2).map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        records.push(HistoryRecord { date: date.to_string(), amount, label });
+    }
+    Ok(records)
+}
+
+/// Comma-separated fields; double quotes protect commas, `""` is a quote.
+pub fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cur.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut cur)),
+            c => cur.push(c),
+        }
+    }
+    fields.push(cur);
+    fields
+}
+
+/// Period of a record date: "YYYY-MM" for monthly series, "YYYY" for annual.
+pub fn record_period(date: &str, monthly: bool) -> Result<String> {
+    let d = date.trim();
+    if monthly {
+        let ym = d.get(..7).filter(|_| d.len() == 7 || d.len() == 10).unwrap_or(d);
+        parse_ym(ym)
+            .ok()
+            .filter(|p| Frequency::of(p) == Some(Frequency::Monthly))
+            .ok_or_else(|| anyhow!("Date '{}' must be YYYY-MM or YYYY-MM-DD", date))
+    } else {
+        Ok(format!("{:04}", parse_year_loose(d).with_context(|| format!("Date '{}' must start with YYYY", date))?))
+    }
+}
+
+// ----------------------- Deflation -----------------------
+/// One record in constant prices of the base period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub period: String,
+    pub label: Option<String>,
+    pub nominal: f64,
+    /// Price level the record is deflated from.
+    pub level: f64,
+    /// `nominal * base_level / level`.
+    pub real: f64,
+}
+
+/// Records of one period added up (several payments may share a month).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryPeriod {
+    pub period: String,
+    pub nominal: f64,
+    pub real: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistorySummary {
+    /// Reference period every entry is expressed in.
+    pub base: IndexPoint,
+    pub entries: Vec<HistoryEntry>,
+    /// Real value per period, in date order.
+    pub path: Vec<HistoryPeriod>,
+    pub total_nominal: f64,
+    pub total_real: f64,
+    /// Periods with the highest and lowest real value.
+    pub best: HistoryPeriod,
+    pub worst: HistoryPeriod,
+}
+
+/// Express every record in prices of `base` (a period of the series'
+/// format; the latest observation when `None`), all from one `series`.
+///
+/// `periods[i]` is the period of `records[i]` (see [`record_period`]); its
+/// price level is read at [`PriceIndexSeries::amount_period`], which differs
+/// from the record period for annual deflators.
+pub fn deflate_history(
+    records: &[HistoryRecord],
+    periods: &[String],
+    series: &PriceIndexSeries,
+    base: Option<&str>,
+) -> Result<HistorySummary> {
+    let base = match base {
+        Some(p) => series
+            .amount_point(p)
+            .ok_or_else(|| anyhow!("No price data for base period {}", p))?,
+        None => series.latest().cloned().ok_or_else(|| anyhow!("No price data found"))?,
+    };
+    let mut entries = Vec::with_capacity(records.len());
+    for (rec, period) in records.iter().zip(periods) {
+        let level = series.level_at(&series.amount_period(period)).ok_or_else(|| {
+            anyhow!(
+                "No price data for {} ({}{})",
+                period,
+                rec.date,
+                rec.label.as_deref().map(|l| format!(", {}", l)).unwrap_or_default()
+            )
+        })?;
+        entries.push(HistoryEntry {
+            period: period.clone(),
+            label: rec.label.clone(),
+            nominal: rec.amount,
+            level,
+            real: rec.amount * base.level / level,
+        });
+    }
+    entries.sort_by(|a, b| a.period.cmp(&b.period));
+
+    let mut by_period: BTreeMap<&str, HistoryPeriod> = BTreeMap::new();
+    for e in &entries {
+        let p = by_period.entry(&e.period).or_insert_with(|| HistoryPeriod {
+            period: e.period.clone(),
+            nominal: 0.0,
+            real: 0.0,
+        });
+        p.nominal += e.nominal;
+        p.real += e.real;
+    }
+    let path: Vec<HistoryPeriod> = by_period.into_values().collect();
+
+    let best = path
+        .iter()
+        .max_by(|a, b| a.real.total_cmp(&b.real))
+        .cloned()
+        .ok_or_else(|| anyhow!("No records"))?;
+    let worst = path
+        .iter()
+        .min_by(|a, b| a.real.total_cmp(&b.real))
+        .cloned()
+        .ok_or_else(|| anyhow!("No records"))?;
+
+    Ok(HistorySummary {
+        base,
+        total_nominal: entries.iter().map(|e| e.nominal).sum(),
+        total_real: entries.iter().map(|e| e.real).sum(),
+        entries,
+        path,
+        best,
+        worst,
+    })
+}
@@ -0,0 +1,295 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cache::parse_duration;
+
+// ----------------------- Settings -----------------------
+/// Timeouts and retry policy shared by every IMF request.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HttpSettings {
+    /// Time allowed to establish the TCP/TLS connection.
+    pub connect_timeout: Duration,
+    /// Maximum silence between two reads of the response.
+    pub read_timeout: Duration,
+    /// Total attempts per request, including the first one (at least 1).
+    pub max_attempts: u32,
+    /// Backoff before the first retry; doubled on each further retry.
+    pub base_delay: Duration,
+    /// Upper bound for one backoff, and for a server's `Retry-After` (a longer
+    /// one is waited out only this long before retrying).
+    pub max_delay: Duration,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// `[http]` table of the config file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    pub connect_timeout: Option<String>,
+    pub read_timeout: Option<String>,
+    pub max_attempts: Option<u32>,
+    pub retry_base_delay: Option<String>,
+    pub retry_max_delay: Option<String>,
+}
+
+impl HttpConfig {
+    pub fn settings(&self) -> Result<HttpSettings> {
+        let mut s = HttpSettings::default();
+        if let Some(v) = &self.connect_timeout {
+            s.connect_timeout = parse_duration(v).context("http.connect_timeout")?;
+        }
+        if let Some(v) = &self.read_timeout {
+            s.read_timeout = parse_duration(v).context("http.read_timeout")?;
+        }
+        if let Some(n) = self.max_attempts {
+            s.max_attempts = n;
+        }
+        if let Some(v) = &self.retry_base_delay {
+            s.base_delay = parse_duration(v).context("http.retry_base_delay")?;
+        }
+        if let Some(v) = &self.retry_max_delay {
+            s.max_delay = parse_duration(v).context("http.retry_max_delay")?;
+        }
+        Ok(s)
+    }
+}
+
+// ----------------------- Provenance -----------------------
+/// Where a piece of data came from, for reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Retrieval {
+    pub url: String,
+    /// When the data was downloaded (for cached data: when it was originally fetched).
+    pub retrieved_at: DateTime<Utc>,
+    /// Final HTTP status (`None` for cached data or transport errors).
+    pub status: Option<u16>,
+    /// Answered from the local cache/series store rather than the network.
+    pub cached: bool,
+}
+
+// ----------------------- Client -----------------------
+/// `reqwest` client with timeouts, plus retries with exponential backoff and
+/// jitter on transient failures (connect errors, timeouts, 429 and 5xx).
+///
+/// Every request (and every cache hit reported by the providers) is logged
+/// as a [`Retrieval`].
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    settings: HttpSettings,
+    log: Arc<Mutex<Vec<Retrieval>>>,
+}
+
+impl HttpClient {
+    /// Apply the timeouts of `settings` to `builder` and build the client.
+    pub fn new(builder: ClientBuilder, settings: HttpSettings) -> Result<Self> {
+        let client = builder
+            .connect_timeout(settings.connect_timeout)
+            .read_timeout(settings.read_timeout)
+            .build()?;
+        Ok(Self {
+            client,
+            settings,
+            log: Arc::default(),
+        })
+    }
+
+    pub fn settings(&self) -> &HttpSettings {
+        &self.settings
+    }
+
+    /// Requests made and cache hits recorded so far, in order.
+    pub fn retrievals(&self) -> Vec<Retrieval> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Record data served from the cache instead of `url` (fetched at
+    /// `fetched_at`); the same entry is only listed once.
+    pub fn record_cached(&self, url: &str, fetched_at: DateTime<Utc>) {
+        let r = Retrieval {
+            url: url.to_string(),
+            retrieved_at: fetched_at,
+            status: None,
+            cached: true,
+        };
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if !log.contains(&r) {
+            log.push(r);
+        }
+    }
+
+    fn push(&self, r: Retrieval) {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).push(r);
+    }
+
+    /// GET `url`, retrying transient failures up to `max_attempts` in total.
+    ///
+    /// Once attempts run out the last response is returned as is (callers
+    /// check its status), or the last transport error.
+    pub async fn get(&self, url: &str) -> reqwest::Result<Response> {
+        let outcome = self.get_with_retries(url).await;
+        self.push(Retrieval {
+            url: url.to_string(),
+            retrieved_at: Utc::now(),
+            status: outcome.as_ref().ok().map(|r| r.status().as_u16()),
+            cached: false,
+        });
+        outcome
+    }
+
+    async fn get_with_retries(&self, url: &str) -> reqwest::Result<Response> {
+        let mut attempt = 1;
+        loop {
+            let outcome = self.client.get(url).send().await;
+            if attempt >= self.settings.max_attempts {
+                return outcome;
+            }
+
+            let delay = match &outcome {
+                Ok(resp) if is_transient(resp.status()) => match retry_after(resp.headers(), Utc::now()) {
+                    // The server asks for more patience than we are willing to
+                    // give: try again after our own limit anyway.
+                    Some(d) => d.min(self.settings.max_delay),
+                    None => self.backoff(attempt),
+                },
+                Ok(_) => return outcome,
+                Err(e) if e.is_connect() || e.is_timeout() => self.backoff(attempt),
+                Err(_) => return outcome,
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// `base_delay * 2^(attempt-1)`, capped at `max_delay`, then jittered to
+    /// a random value in its upper half so clients do not retry in lockstep.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.settings.base_delay.saturating_mul(1 << (attempt - 1).min(16));
+        let cap = exp.min(self.settings.max_delay);
+        cap / 2 + cap.mul_f64(rand::thread_rng().gen_range(0.0..=0.5))
+    }
+}
+
+/// Statuses worth retrying: rate limiting and server-side failures.
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// `Retry-After` as delay-seconds ("120") or an HTTP date.
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let v = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = v.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(v).ok()?.with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{Faults, FixtureServer};
+    use std::time::Instant;
+    use tokio::net::TcpListener;
+
+    const PATH: &str = "/datamapper/PCPIPCH/ITA";
+
+    fn client() -> HttpClient {
+        let settings = HttpSettings {
+            connect_timeout: Duration::from_secs(2),
+            read_timeout: Duration::from_millis(200),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+        };
+        HttpClient::new(Client::builder(), settings).unwrap()
+    }
+
+    /// Serve the fixtures with `faults` on an ephemeral port; returns a handle
+    /// sharing the server's hit counts, and the URL of [`PATH`].
+    async fn serve(faults: Faults) -> (FixtureServer, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), PATH);
+        let server = FixtureServer::new(None).with_faults(faults);
+        tokio::spawn(server.clone().serve(listener));
+        (server, url)
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_the_fixture_is_served() {
+        let (server, url) = serve(Faults {
+            status: Some(503),
+            times: 2,
+            ..Faults::default()
+        })
+        .await;
+
+        let resp = client().get(&url).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(server.hits(PATH), 3);
+    }
+
+    #[tokio::test]
+    async fn caps_a_long_retry_after_at_max_delay() {
+        let (server, url) = serve(Faults {
+            status: Some(429),
+            times: 1,
+            retry_after: Some(30),
+            ..Faults::default()
+        })
+        .await;
+
+        let started = Instant::now();
+        let resp = client().get(&url).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(server.hits(PATH), 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn returns_the_last_rate_limit_once_attempts_run_out() {
+        let (server, url) = serve(Faults {
+            status: Some(429),
+            retry_after: Some(1),
+            ..Faults::default()
+        })
+        .await;
+
+        let client = client();
+        let resp = client.get(&url).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(server.hits(PATH), 3);
+        assert_eq!(client.retrievals()[0].status, Some(429));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_repeated_timeouts() {
+        let (server, url) = serve(Faults {
+            delay: Some(Duration::from_secs(2)),
+            ..Faults::default()
+        })
+        .await;
+
+        let err = client().get(&url).await.unwrap_err();
+        assert!(err.is_timeout(), "{err}");
+        assert_eq!(server.hits(PATH), 3);
+    }
+}
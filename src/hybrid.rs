@@ -0,0 +1,351 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+use crate::datamapper::{DataMapperProvider, DATAMAPPER_INDICATOR};
+use crate::http::Retrieval;
+use crate::period::{current_ym, parse_year_loose, shift_period, PeriodRange};
+use crate::provider::CpiProvider;
+use crate::sdmx::SdmxProvider;
+use crate::series::{IndexPoint, Item, PriceIndexSeries, SeriesSegment};
+
+// ----------------------- Constants -----------------------
+/// Months behind the current one that are normally still unpublished: a
+/// trailing SDMX gap within this window is a publication lag, not missing data.
+pub const PUBLICATION_LAG_MONTHS: i32 = 3;
+
+/// Level given to the first month when no SDMX observation anchors the series.
+const FALLBACK_BASE_LEVEL: f64 = 100.0;
+
+/// Segment sources, as listed in [`PriceIndexSeries::segments`].
+pub const HYBRID_SDMX_SOURCE: &str = "IMF SDMX (CPI index level)";
+pub const HYBRID_DATAMAPPER_SOURCE: &str = "IMF DataMapper (PCPIPCH, chained monthly)";
+/// Months in the requested range that neither source covers (left out).
+pub const HYBRID_MISSING_SOURCE: &str = "no data (left out of the series)";
+
+// ----------------------- Provider -----------------------
+/// Monthly SDMX CPI wherever it exists, with missing leading or trailing
+/// months chained from DataMapper PCPIPCH annual rates (spread evenly over
+/// the months of each year: `(1 + PCPIPCH / 100)^(1/12)` per month).
+///
+/// The returned series lists which stretch came from which source in
+/// [`PriceIndexSeries::segments`].
+pub struct HybridProvider {
+    sdmx: SdmxProvider,
+    datamapper: DataMapperProvider,
+}
+
+impl HybridProvider {
+    pub fn new(sdmx: SdmxProvider, datamapper: DataMapperProvider) -> Self {
+        Self { sdmx, datamapper }
+    }
+
+    pub fn sdmx(&self) -> &SdmxProvider {
+        &self.sdmx
+    }
+}
+
+#[async_trait]
+impl CpiProvider for HybridProvider {
+    fn source_label(&self) -> &'static str {
+        "IMF SDMX + DataMapper"
+    }
+
+    fn indicator(&self) -> &'static str {
+        "CPI index level (PCPIPCH-chained where missing)"
+    }
+
+    async fn list_countries(&self) -> Result<Vec<Item>> {
+        self.sdmx.list_countries().await
+    }
+
+    async fn fetch_series(&self, country: &str, range: &PeriodRange) -> Result<PriceIndexSeries> {
+        // Only missing data is filled in: a failed request (timeout, 5xx,
+        // unparsable body) is an error, not a reason to switch sources.
+        let sdmx_points = self
+            .sdmx
+            .fetch_points(country, range)
+            .await
+            .context("SDMX request failed (not falling back to DataMapper)")?;
+
+        // Trailing months are only filled up to the end of the publication lag.
+        let fill_end = shift_period(&current_ym(), -PUBLICATION_LAG_MONTHS)
+            .map_or(range.end.clone(), |lag_end| lag_end.min(range.end.clone()));
+        let (need_lead, need_trail) = gaps(range, &fill_end, &sdmx_points);
+        if !need_lead && !need_trail {
+            return hybrid_series(country, sdmx_points, Vec::new());
+        }
+
+        // One DataMapper request for every year to fill; no rates at all
+        // leaves the gaps unfilled (and listed), a failed request is an error.
+        let first_year = parse_year_loose(&range.start)?;
+        let last_year = parse_year_loose(&fill_end)?.max(first_year);
+        let rates: HashMap<i32, f64> = self
+            .datamapper
+            .fetch_pcpipch_rates(country, first_year, last_year)
+            .await
+            .with_context(|| {
+                format!(
+                    "DataMapper request failed (filling SDMX gaps for {} over {} → {})",
+                    country, range.start, range.end
+                )
+            })?
+            .into_iter()
+            .map(|yi| (yi.year, yi.pct))
+            .collect();
+
+        if sdmx_points.is_empty() && rates.is_empty() {
+            return Err(anyhow!(
+                "No SDMX CPI data for {} over {} → {}, and no {} rates to fill in either",
+                country,
+                range.start,
+                range.end,
+                DATAMAPPER_INDICATOR
+            ));
+        }
+        splice(country, range, &fill_end, sdmx_points, &rates)
+    }
+
+    fn retrievals(&self) -> Vec<Retrieval> {
+        let mut all = self.sdmx.retrievals();
+        all.extend(self.datamapper.retrievals());
+        all
+    }
+}
+
+/// Whether months are missing before the first SDMX point (from
+/// `range.start`) and after the last one (up to `fill_end`).
+fn gaps(range: &PeriodRange, fill_end: &str, sdmx_points: &[IndexPoint]) -> (bool, bool) {
+    let lead_end = sdmx_points.first().and_then(|p| shift_period(&p.period, -1));
+    let trail_start = match sdmx_points.last() {
+        Some(p) => shift_period(&p.period, 1),
+        None => Some(range.start.clone()),
+    };
+    (
+        lead_end.is_some_and(|e| e.as_str() >= range.start.as_str()),
+        trail_start.is_some_and(|s| s.as_str() <= fill_end),
+    )
+}
+
+/// Chain the missing months around `sdmx_points` from the annual `rates`
+/// (year → PCPIPCH %). Months with no rate are left out and listed as
+/// [`HYBRID_MISSING_SOURCE`] segments.
+pub fn splice(
+    country: &str,
+    range: &PeriodRange,
+    fill_end: &str,
+    sdmx_points: Vec<IndexPoint>,
+    rates: &HashMap<i32, f64>,
+) -> Result<PriceIndexSeries> {
+    let (need_lead, need_trail) = gaps(range, fill_end, &sdmx_points);
+    let monthly = |period: &str| -> Option<f64> {
+        let year = parse_year_loose(period).ok()?;
+        rates.get(&year).map(|pct| (1.0 + pct / 100.0).powf(1.0 / 12.0))
+    };
+
+    let mut segments = Vec::new();
+
+    // ---- leading months: chain backwards from the first SDMX level ----
+    let mut lead = Vec::new();
+    if let (true, Some(first)) = (need_lead, sdmx_points.first()) {
+        let mut next = first.clone();
+        while let Some(period) = shift_period(&next.period, -1).filter(|p| p.as_str() >= range.start.as_str()) {
+            // Growth into `next` happened in `next`'s year.
+            let Some(factor) = monthly(&next.period) else { break };
+            next = IndexPoint {
+                period,
+                level: next.level / factor,
+            };
+            lead.push(next.clone());
+        }
+        lead.reverse();
+
+        let covered_from = lead.first().unwrap_or(first);
+        if let Some(end) = shift_period(&covered_from.period, -1).filter(|e| e.as_str() >= range.start.as_str()) {
+            segments.push(missing(&range.start, &end));
+        }
+    }
+    if let (Some(first), Some(last)) = (lead.first(), lead.last()) {
+        segments.push(segment(HYBRID_DATAMAPPER_SOURCE, first, last));
+    }
+
+    let mut points = lead;
+    if let (Some(first), Some(last)) = (sdmx_points.first(), sdmx_points.last()) {
+        segments.push(segment(HYBRID_SDMX_SOURCE, first, last));
+    }
+    points.extend(sdmx_points);
+
+    // ---- trailing months: chain forwards from the last level ----
+    if need_trail {
+        let mut trail = Vec::new();
+        let mut prev = match points.last() {
+            Some(p) => p.clone(),
+            // No SDMX data at all: the range start is the anchor.
+            None => {
+                let anchor = IndexPoint {
+                    period: range.start.clone(),
+                    level: FALLBACK_BASE_LEVEL,
+                };
+                trail.push(anchor.clone());
+                anchor
+            }
+        };
+        while let Some(period) = shift_period(&prev.period, 1).filter(|p| p.as_str() <= fill_end) {
+            let Some(factor) = monthly(&period) else { break };
+            prev = IndexPoint {
+                level: prev.level * factor,
+                period,
+            };
+            trail.push(prev.clone());
+        }
+        if let (Some(first), Some(last)) = (trail.first(), trail.last()) {
+            segments.push(segment(HYBRID_DATAMAPPER_SOURCE, first, last));
+        }
+        points.extend(trail);
+
+        if let Some(start) = shift_period(&prev.period, 1).filter(|s| s.as_str() <= fill_end) {
+            segments.push(missing(&start, fill_end));
+        }
+    }
+
+    hybrid_series(country, points, segments)
+}
+
+/// Series starting at the first of `points`; `segments` are only kept when
+/// DataMapper filled something in.
+fn hybrid_series(country: &str, points: Vec<IndexPoint>, segments: Vec<SeriesSegment>) -> Result<PriceIndexSeries> {
+    let first = points.first().ok_or_else(|| anyhow!("No CPI data found"))?;
+    Ok(PriceIndexSeries {
+        country: country.to_string(),
+        start_period: first.period.clone(),
+        base_level: first.level,
+        yearly: Vec::new(),
+        segments: if segments.iter().any(|s| s.source != HYBRID_SDMX_SOURCE) {
+            segments
+        } else {
+            Vec::new()
+        },
+        points,
+    })
+}
+
+fn missing(start: &str, end: &str) -> SeriesSegment {
+    SeriesSegment {
+        source: HYBRID_MISSING_SOURCE.to_string(),
+        start: start.to_string(),
+        end: end.to_string(),
+    }
+}
+
+fn segment(source: &str, first: &IndexPoint, last: &IndexPoint) -> SeriesSegment {
+    SeriesSegment {
+        source: source.to_string(),
+        start: first.period.clone(),
+        end: last.period.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: &str, end: &str) -> PeriodRange {
+        PeriodRange {
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    fn points(levels: &[(&str, f64)]) -> Vec<IndexPoint> {
+        levels
+            .iter()
+            .map(|&(period, level)| IndexPoint {
+                period: period.to_string(),
+                level,
+            })
+            .collect()
+    }
+
+    fn level(series: &PriceIndexSeries, period: &str) -> f64 {
+        series.points.iter().find(|p| p.period == period).unwrap().level
+    }
+
+    fn sources(series: &PriceIndexSeries) -> Vec<(&str, &str, &str)> {
+        series
+            .segments
+            .iter()
+            .map(|s| (s.source.as_str(), s.start.as_str(), s.end.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn chains_leading_and_trailing_months_from_annual_rates() {
+        let rates = HashMap::from([(2022, 12.0), (2023, 6.0)]);
+        let sdmx = points(&[("2022-12", 100.0), ("2023-01", 101.0)]);
+        let s = splice("ITA", &range("2022-10", "2023-03"), "2023-03", sdmx, &rates).unwrap();
+
+        let m2022 = 1.12_f64.powf(1.0 / 12.0);
+        let m2023 = 1.06_f64.powf(1.0 / 12.0);
+        assert!((level(&s, "2022-11") - 100.0 / m2022).abs() < 1e-9);
+        assert!((level(&s, "2022-10") - 100.0 / m2022 / m2022).abs() < 1e-9);
+        assert!((level(&s, "2023-02") - 101.0 * m2023).abs() < 1e-9);
+        assert!((level(&s, "2023-03") - 101.0 * m2023 * m2023).abs() < 1e-9);
+        assert_eq!(s.start_period, "2022-10");
+        assert_eq!(s.base_level, level(&s, "2022-10"));
+        assert_eq!(
+            sources(&s),
+            [
+                (HYBRID_DATAMAPPER_SOURCE, "2022-10", "2022-11"),
+                (HYBRID_SDMX_SOURCE, "2022-12", "2023-01"),
+                (HYBRID_DATAMAPPER_SOURCE, "2023-02", "2023-03"),
+            ]
+        );
+    }
+
+    #[test]
+    fn twelve_chained_months_compound_to_the_annual_rate() {
+        let rates = HashMap::from([(2023, 5.0)]);
+        let sdmx = points(&[("2022-12", 100.0)]);
+        let s = splice("ITA", &range("2022-12", "2023-12"), "2023-12", sdmx, &rates).unwrap();
+        assert_eq!(s.points.len(), 13);
+        assert!((level(&s, "2023-12") - 105.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn months_without_a_rate_are_listed_as_missing() {
+        let rates = HashMap::from([(2019, 3.0)]);
+        let sdmx = points(&[("2019-01", 100.0), ("2019-02", 100.2)]);
+        let s = splice("ITA", &range("2017-06", "2020-02"), "2020-02", sdmx, &rates).unwrap();
+
+        // The requested start moves to the first month that could be chained.
+        assert_eq!(s.start_period, "2018-12");
+        assert_eq!(s.points.last().unwrap().period, "2019-12");
+        assert_eq!(
+            sources(&s),
+            [
+                (HYBRID_MISSING_SOURCE, "2017-06", "2018-11"),
+                (HYBRID_DATAMAPPER_SOURCE, "2018-12", "2018-12"),
+                (HYBRID_SDMX_SOURCE, "2019-01", "2019-02"),
+                (HYBRID_DATAMAPPER_SOURCE, "2019-03", "2019-12"),
+                (HYBRID_MISSING_SOURCE, "2020-01", "2020-02"),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_sdmx_data_the_range_start_anchors_the_chain() {
+        let rates = HashMap::from([(2024, 2.4)]);
+        let s = splice("ITA", &range("2024-01", "2024-03"), "2024-03", Vec::new(), &rates).unwrap();
+        assert_eq!(level(&s, "2024-01"), FALLBACK_BASE_LEVEL);
+        assert!((level(&s, "2024-03") - FALLBACK_BASE_LEVEL * 1.024_f64.powf(2.0 / 12.0)).abs() < 1e-9);
+        assert_eq!(sources(&s), [(HYBRID_DATAMAPPER_SOURCE, "2024-01", "2024-03")]);
+    }
+
+    #[test]
+    fn complete_sdmx_data_has_no_segments() {
+        let sdmx = points(&[("2024-01", 100.0), ("2024-02", 100.5)]);
+        let s = splice("ITA", &range("2024-01", "2024-02"), "2024-02", sdmx, &HashMap::new()).unwrap();
+        assert!(s.segments.is_empty());
+        assert_eq!(s.points.len(), 2);
+    }
+}
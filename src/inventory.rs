@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::{meta_path, read_checked, read_meta, Cache, META_SUFFIX};
+use crate::sdmx::parse_codelist;
+use crate::structure::parse_structure;
+use crate::series::Item;
+use crate::store::StoredSeries;
+
+// ----------------------- Cache inventory -----------------------
+/// What a cached file holds, which decides how it is parsed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    /// Raw SDMX-ML codelist (countries or COICOP divisions).
+    SdmxCodelist,
+    /// Raw SDMX-ML dataflow structure (DSD and its codelists).
+    SdmxStructure,
+    /// DataMapper country list (code + label).
+    DataMapperCountries,
+    /// Canonical observation series (see [`crate::store`]).
+    Series,
+    /// Anything else (e.g. per-query files from older versions).
+    Other,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::SdmxCodelist => "sdmx-codelist",
+            Self::SdmxStructure => "sdmx-structure",
+            Self::DataMapperCountries => "dm-countries",
+            Self::Series => "series",
+            Self::Other => "other",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// Path relative to the cache dir, with `/` separators (used to address entries).
+    pub name: String,
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub source_url: Option<String>,
+}
+
+pub fn kind_of(name: &str) -> EntryKind {
+    match name {
+        "sdmx_countries_iso3.xml" | "sdmx_coicop.xml" => EntryKind::SdmxCodelist,
+        "dm_countries.json" => EntryKind::DataMapperCountries,
+        n if n.starts_with("sdmx_structure_") && n.ends_with(".xml") => EntryKind::SdmxStructure,
+        n if n.starts_with("series/") && n.ends_with(".json") => EntryKind::Series,
+        _ => EntryKind::Other,
+    }
+}
+
+fn walk(dir: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    let rd = match fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(anyhow!(e).context(format!("Cannot list {}", dir.display()))),
+    };
+    for de in rd {
+        let path = de?.path();
+        if path.is_dir() {
+            walk(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl Cache {
+    /// Every file under the cache dir, sorted by name.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut files = Vec::new();
+        walk(self.dir(), &mut files)?;
+
+        // Sidecars describe entries; dot-files are in-flight temp writes.
+        let mut out: Vec<CacheEntry> = files
+            .into_iter()
+            .filter(|p| {
+                let n = p.file_name().unwrap_or_default().to_string_lossy();
+                !n.ends_with(META_SUFFIX) && !n.starts_with('.')
+            })
+            .filter_map(|p| self.describe(p).ok())
+            .collect();
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(out)
+    }
+
+    /// Entry by the name shown in `entries()`.
+    pub fn entry(&self, name: &str) -> Result<CacheEntry> {
+        let rel = name.trim().trim_start_matches('/');
+        if rel.split('/').any(|seg| seg == "..") {
+            return Err(anyhow!("Invalid cache entry name '{}'", name));
+        }
+        let path = self.dir().join(rel);
+        if !path.is_file() {
+            return Err(anyhow!("No cache entry '{}' in {}", name, self.dir().display()));
+        }
+        self.describe(path)
+    }
+
+    fn describe(&self, path: PathBuf) -> Result<CacheEntry> {
+        let meta = fs::metadata(&path)?;
+        let name = path
+            .strip_prefix(self.dir())
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let kind = kind_of(&name);
+
+        let (fetched_at, source_url) = match read_meta(&path) {
+            Some(m) => (Some(m.fetched_at), Some(m.url)),
+            None => (meta.modified().ok().map(DateTime::<Utc>::from), None),
+        };
+
+        Ok(CacheEntry {
+            name,
+            path,
+            kind,
+            size: meta.len(),
+            fetched_at,
+            source_url,
+        })
+    }
+
+    /// Delete one entry and its sidecar.
+    pub fn remove(&self, entry: &CacheEntry) -> Result<()> {
+        let _ = fs::remove_file(meta_path(&entry.path));
+        fs::remove_file(&entry.path).with_context(|| format!("Cannot delete {}", entry.path.display()))
+    }
+
+    /// Delete every entry; returns how many files were removed.
+    pub fn clear(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        for e in &entries {
+            self.remove(e)?;
+        }
+        let _ = fs::remove_dir_all(self.dir().join("series"));
+        Ok(entries.len())
+    }
+}
+
+/// Check an entry against its sidecar hash and re-parse it with the same
+/// parser the providers use.
+///
+/// `Ok(false)` means the kind has no parser (not checked).
+pub fn verify(entry: &CacheEntry) -> Result<bool> {
+    let b = read_checked(&entry.path)?;
+    validate(entry.kind, &b)
+}
+
+/// Parse `bytes` as an entry of `kind`; `Ok(false)` if the kind has no parser.
+pub fn validate(kind: EntryKind, bytes: &[u8]) -> Result<bool> {
+    match kind {
+        EntryKind::SdmxCodelist => {
+            parse_codelist(bytes)?;
+        }
+        EntryKind::SdmxStructure => {
+            parse_structure(bytes)?;
+        }
+        EntryKind::DataMapperCountries => {
+            let items: Vec<Item> = serde_json::from_slice(bytes).context("Invalid DataMapper country list")?;
+            if items.is_empty() {
+                return Err(anyhow!("Empty DataMapper country list"));
+            }
+        }
+        EntryKind::Series => {
+            let s: StoredSeries = serde_json::from_slice(bytes).context("Invalid stored series")?;
+            if s.observations.is_empty() {
+                return Err(anyhow!("Stored series has no observations"));
+            }
+            if let Some((p, v)) = s.observations.iter().find(|(_, v)| !v.is_finite()) {
+                return Err(anyhow!("Non-numeric value {} at {}", v, p));
+            }
+        }
+        EntryKind::Other => return Ok(false),
+    }
+    Ok(true)
+}
@@ -0,0 +1,59 @@
+//! RIP library: inflation-adjusted (real) value of income from IMF data.
+//!
+//! The CLI in `main.rs` is a thin layer over this crate. Embedders pick a
+//! [`CpiProvider`] (SDMX monthly CPI, DataMapper annual inflation, or a hybrid
+//! of both), fetch a
+//! [`PriceIndexSeries`] and feed its levels to [`compute_real_value`]:
+//!
+//! ```no_run
+//! # async fn demo() -> anyhow::Result<()> {
+//! use rip::{compute_real_value, CpiProvider, PeriodRange, SdmxProvider};
+//!
+//! let cache = rip::Cache::new(rip::default_cache_dir()?, rip::CachePolicy::Use, Default::default());
+//! let provider = SdmxProvider::new(rip::sdmx::build_client(Default::default())?, cache);
+//! let range = PeriodRange::monthly("2021-01", None)?;
+//! let series = provider.fetch_series("ITA", &range).await?;
+//! let latest = series.latest().unwrap();
+//! let r = compute_real_value(50_000.0, series.base_level, latest.level);
+//! println!("{} -> {:.2}", latest.period, r.real);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod basket;
+pub mod bundle;
+pub mod cache;
+pub mod chart;
+pub mod compute;
+pub mod config;
+pub mod datamapper;
+pub mod decompose;
+pub mod fixtures;
+pub mod font;
+pub mod history;
+pub mod http;
+pub mod hybrid;
+pub mod inventory;
+pub mod output;
+pub mod period;
+pub mod plot;
+pub mod provider;
+pub mod report;
+pub mod sdmx;
+pub mod series;
+pub mod store;
+pub mod structure;
+
+pub use cache::{default_cache_dir, Cache, CachePolicy, CacheTtls};
+pub use compute::{
+    adjust_amount, compare_raise, compute_inflated_value, compute_raise_needed, compute_real_raise, compute_real_value,
+    real_value_series, AdjustedAmount, InflatedValue, RaiseComparison, RaiseNeeded, RealRaise, RealValue, RealValuePoint,
+};
+pub use config::{Config, Endpoints};
+pub use datamapper::DataMapperProvider;
+pub use http::{HttpClient, HttpSettings, Retrieval};
+pub use hybrid::HybridProvider;
+pub use period::{Frequency, PeriodRange};
+pub use provider::{fetch_with_base, CpiProvider};
+pub use sdmx::SdmxProvider;
+pub use series::{IndexPoint, Item, PriceIndexSeries, SeriesSegment, YearInflation};
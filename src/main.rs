@@ -1,56 +1,125 @@
 use anyhow::{anyhow, Context, Result};
-use chrono::{Datelike, NaiveDate};
-use clap::{Parser, ValueEnum};
-use dialoguer::{theme::ColorfulTheme, FuzzySelect, Input, Select};
+use clap::{Parser, Subcommand, ValueEnum};
+use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, MultiSelect, Select};
 use num_format::{Locale, ToFormattedString};
 use rand::seq::SliceRandom;
-use reqwest::header::{ACCEPT, ACCEPT_LANGUAGE, REFERER};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::fs;
-use std::path::{Path, PathBuf};
-
-// NEW: XML parser for SDMX-ML responses
-use quick_xml::events::Event;
-use quick_xml::Reader;
-
-// ----------------------- Constants -----------------------
-const IMF_SDMX_BASE: &str = "https://api.imf.org/external/sdmx/2.1";
-const IMF_SDMX_STRUCTURE_BASE: &str = "https://sdmxcentral.imf.org/ws/public/sdmxapi/rest";
-const IMF_DATAMAPPER_BASE: &str = "https://www.imf.org/external/datamapper/api/v1";
-
-// ----------------------- SDMX CPI dataset (NEW STYLE KEY) -----------------------
-// Your example:
-//   /data/CPI/RUS.CPI._T.IX.M?startPeriod=2020-M01
-// Key parts:
-//   COUNTRY.INDEX_TYPE.COICOP_1999.TYPE_OF_TRANSFORMATION.FREQUENCY
-const SDMX_CPI_DATASET: &str = "CPI";
-const SDMX_CPI_INDEX_TYPE: &str = "CPI"; // headline index family
-const SDMX_CPI_COICOP: &str = "_T"; // all-items
-const SDMX_CPI_TRANSFORMATION: &str = "IX"; // index level
-const SDMX_CPI_FREQ: &str = "M"; // monthly
-
-// SDMX codelist for CPI areas
-const SDMX_CL_AREA_CPI: &str = "CL_COUNTRY_ISO3";
-
-// DataMapper fixed indicator for annual inflation rate
-const DATAMAPPER_INDICATOR: &str = "PCPIPCH"; // annual inflation (%), avg consumer prices
+use std::path::PathBuf;
+
+use rip::basket::{compare_basket, read_basket, BasketComparison, BasketItem};
+use rip::bundle;
+use rip::cache::parse_duration;
+use rip::chart::{Chart, ChartLine};
+use rip::datamapper::{self, YearConvention, DATAMAPPER_INDICATOR};
+use rip::decompose::{decompose_headline, DivisionData, HeadlineDecomposition};
+use rip::fixtures::{Faults, FixtureServer};
+use rip::history::{deflate_history, read_history, record_period, HistoryRecord};
+use rip::hybrid::HYBRID_SDMX_SOURCE;
+use rip::inventory;
+use rip::output::{
+    history_to_csv, print_document, series_to_csv, BasketDoc, ComponentResult, ComponentsDoc, CpiLevels,
+    DecompositionDoc, DocumentFormat, HistoryDoc, IndexResult, RaiseDoc, RequestedPeriods, ResultDoc, UsedPeriods, BASKET_FORMAT,
+    COMPONENTS_FORMAT, DECOMPOSITION_FORMAT, HISTORY_FORMAT, RAISE_FORMAT, RESULT_FORMAT, RESULT_VERSION,
+};
+use rip::period::{parse_year_loose, parse_ym, Frequency};
+use rip::plot;
+use rip::report::{Report, ReportTable};
+use rip::sdmx::{self, SDMX_CPI_DATASET, SDMX_CPI_INDEX_TYPE};
+use rip::structure::SeriesKey;
+use rip::{
+    adjust_amount, compare_raise, compute_inflated_value, compute_real_value, default_cache_dir, fetch_with_base,
+    real_value_series, AdjustedAmount, Cache, CachePolicy, Config, CpiProvider, DataMapperProvider, Endpoints,
+    HttpSettings, HybridProvider, IndexPoint, InflatedValue, Item, PeriodRange, PriceIndexSeries, RaiseComparison,
+    RaiseNeeded, RealValue, RealValuePoint, SdmxProvider, SeriesSegment,
+};
 
 // ----------------------- CLI -----------------------
-#[derive(Copy, Clone, Debug, ValueEnum)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
 enum Mode {
     Sdmx,
     Datamapper,
+    Hybrid,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human-readable report
+    #[default]
+    Text,
+    /// Versioned JSON document (see `rip::output::ResultDoc`)
+    Json,
+    /// Same document as YAML
+    Yaml,
+    /// Per-period rows (requires --series)
+    Csv,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum PlotStyle {
+    /// Line charts of the price level and of the real value
+    Line,
+    /// One-line sparklines
+    Spark,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Direction {
+    /// What an amount from the start period is worth in today's money
+    #[default]
+    Deflate,
+    /// What an amount from the start period costs in today's money
+    Inflate,
+}
+
+impl Direction {
+    fn id(self) -> &'static str {
+        match self {
+            Direction::Deflate => "deflate",
+            Direction::Inflate => "inflate",
+        }
+    }
+
+    /// Name of the adjusted amount in tables and charts.
+    fn value_label(self) -> &'static str {
+        match self {
+            Direction::Deflate => "Real value",
+            Direction::Inflate => "Equivalent",
+        }
+    }
+}
+
+impl Format {
+    /// Document format of the machine formats (`None` for text and CSV).
+    fn document(self) -> Option<DocumentFormat> {
+        match self {
+            Format::Json => Some(DocumentFormat::Json),
+            Format::Yaml => Some(DocumentFormat::Yaml),
+            Format::Text | Format::Csv => None,
+        }
+    }
+}
+
+impl Mode {
+    fn id(self) -> &'static str {
+        match self {
+            Mode::Sdmx => "sdmx",
+            Mode::Datamapper => "datamapper",
+            Mode::Hybrid => "hybrid",
+        }
+    }
 }
 
 #[derive(Parser, Debug)]
 #[command(
     name = "rip",
-    about = "Compute the inflation-adjusted (real) value of your income using IMF SDMX (monthly CPI index) or IMF DataMapper (annual inflation)."
+    about = "Compute the inflation-adjusted (real) value of your income using IMF SDMX (monthly CPI index), IMF DataMapper (annual inflation), or a hybrid of both."
 )]
 struct Args {
-    /// Mode: sdmx (monthly CPI index, most precise) or datamapper (annual inflation approximation)
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Mode: sdmx (monthly CPI index, most precise), datamapper (annual inflation approximation)
+    /// or hybrid (SDMX, with months it lacks chained from DataMapper rates)
     #[arg(long, value_enum)]
     mode: Option<Mode>,
 
@@ -61,7 +130,7 @@ struct Args {
     country: Option<String>,
 
     /// Start date:
-    /// - SDMX: YYYY-MM
+    /// - SDMX: YYYY-MM (YYYY-Qn or YYYY for --frequency Q or A)
     /// - DataMapper: YYYY or YYYY-MM (month ignored)
     #[arg(long)]
     start: Option<String>,
@@ -70,15 +139,157 @@ struct Args {
     #[arg(long)]
     amount: Option<f64>,
 
+    /// Direction: deflate (what --amount from the start is worth today) or
+    /// inflate (what --amount from the start costs in today's money)
+    #[arg(long, value_enum, default_value_t = Direction::Deflate)]
+    direction: Direction,
+
+    /// Current nominal amount (e.g. today's salary): also report the raise
+    /// still needed to match --amount in real terms
+    #[arg(long)]
+    current: Option<f64>,
+
+    /// Real raise calculator: compare an old and a new amount, each given as
+    /// DATE:AMOUNT (e.g. --raise 2020-03:40000 2024-09:46000)
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["start", "end", "amount", "direction", "current", "series", "plot", "chart", "compare", "report"]
+    )]
+    raise: Vec<String>,
+
+    /// Salary history: a .csv (date,amount[,label]) or .json file of dated
+    /// payments, all expressed in prices of one reference period
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["start", "end", "amount", "direction", "current", "raise", "series", "plot", "chart", "compare", "report"]
+    )]
+    history: Option<PathBuf>,
+
+    /// SDMX: CPI components (COICOP divisions, e.g. CP01,CP04) instead of all
+    /// items; without a value, pick them from the COICOP codelist. Several
+    /// divisions are compared side by side
+    #[arg(
+        long,
+        value_name = "CODES",
+        num_args = 0..,
+        value_delimiter = ',',
+        conflicts_with_all = ["raise", "history"]
+    )]
+    coicop: Option<Vec<String>>,
+
+    /// SDMX: personal basket, a .csv (coicop,weight[,label]) or .json file of
+    /// COICOP divisions and spending weights; its chain-linked index is
+    /// compared with headline CPI
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["raise", "history", "coicop", "base", "current", "plot", "chart", "compare", "report"]
+    )]
+    basket: Option<PathBuf>,
+
+    /// SDMX: contribution of each COICOP division to headline inflation over
+    /// --start/--end, from the official CPI weights
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = [
+            "amount", "direction", "current", "raise", "history", "coicop", "basket", "base",
+            "series", "plot", "chart", "compare", "report"
+        ]
+    )]
+    decompose: bool,
+
+    /// SDMX: index type of the CPI dataset (INDEX_TYPE dimension), e.g. HICP
+    /// (default: CPI, the national consumer price index)
+    #[arg(long, value_name = "TYPE")]
+    index_type: Option<String>,
+
+    /// SDMX: series frequency, M (monthly, default), Q (quarterly) or A
+    /// (annual), for countries without a monthly CPI; dates may then be
+    /// given as YYYY-Qn or YYYY
+    #[arg(long, value_name = "FREQ")]
+    frequency: Option<Frequency>,
+
+    /// SDMX: deflate with a series of another IMF dataflow (e.g. PPI) instead
+    /// of the CPI; needs --key
+    #[arg(
+        long,
+        value_name = "ID",
+        requires = "key",
+        conflicts_with_all = ["country", "coicop", "basket", "decompose", "index_type", "frequency", "compare"]
+    )]
+    dataflow: Option<String>,
+
+    /// SDMX: series key of --dataflow, dot-separated (ITA.PPI.IX.M) or as
+    /// DIMENSION=CODE pairs (COUNTRY=ITA,INDICATOR=PPI,...); checked against
+    /// the dataflow's data structure definition
+    #[arg(long, value_name = "KEY", requires = "dataflow")]
+    key: Option<String>,
+
+    /// Reference period results are expressed in ("in 2015 prices"):
+    /// YYYY-MM (or a period of --frequency), or YYYY for DataMapper (default:
+    /// the latest observation)
+    #[arg(long)]
+    base: Option<String>,
+
     /// Optional end:
-    /// - SDMX: YYYY-MM
+    /// - SDMX: YYYY-MM (YYYY-Qn or YYYY for --frequency Q or A)
     /// - DataMapper: YYYY (or YYYY-MM)
     #[arg(long)]
     end: Option<String>,
 
-    /// Use disk cache (default true)
-    #[arg(long, default_value_t = true)]
-    cache: bool,
+    /// DataMapper: apply only the months of the first and last years covered
+    /// by --start/--end (geometric pro-rating of the annual rates)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["raise", "history"])]
+    prorate: bool,
+
+    /// DataMapper year-boundary convention: start-inclusive (default; a
+    /// same-year comparison applies a full year of inflation), start-exclusive
+    /// or average-price-level (ratio of the years' average price levels;
+    /// both give zero within the same year)
+    #[arg(long, conflicts_with_all = ["prorate", "raise", "history"])]
+    year_convention: Option<YearConvention>,
+
+    /// Cache policy: use (default; honour TTLs), refresh (always refetch),
+    /// off (no disk cache) or offline (cached data only, never the network)
+    #[arg(long, env = "RIP_CACHE")]
+    cache: Option<CachePolicy>,
+
+    /// Strict offline mode: answer only from local data (cache or imported bundle),
+    /// never touch the network (same as --cache offline)
+    #[arg(long, env = "RIP_OFFLINE", default_value_t = false, conflicts_with = "cache")]
+    offline: bool,
+
+    /// Output format: text, or a versioned json/yaml document on stdout
+    /// (headers and jokes are never printed in machine formats)
+    #[arg(long, value_enum, env = "RIP_FORMAT", default_value_t = Format::Text)]
+    format: Format,
+
+    /// Series mode: every period with its level, MoM/YoY change, cumulative
+    /// inflation and real value (as a table, CSV or in the JSON/YAML document)
+    #[arg(long, default_value_t = false)]
+    series: bool,
+
+    /// Chart the price level and the real value over the range in the terminal
+    /// (line charts, or one-line sparklines with --plot spark)
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "line")]
+    plot: Option<PlotStyle>,
+
+    /// Export a nominal vs real value chart to an image file (.svg or .png)
+    #[arg(long, value_name = "FILE")]
+    chart: Option<PathBuf>,
+
+    /// Also plot the real value for this country on the chart (repeatable)
+    #[arg(long = "compare", value_name = "COUNTRY")]
+    compare: Vec<String>,
+
+    /// Write a self-contained report (.html or .md) with results, table,
+    /// formula, chart and data provenance
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
 
     /// Disable jokes
     #[arg(long, default_value_t = false)]
@@ -87,19 +298,181 @@ struct Args {
     /// Print debug info
     #[arg(long, default_value_t = false)]
     verbose: bool,
+
+    /// Config file (default: <config dir>/rip/config.toml)
+    #[arg(long, env = "RIP_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// IMF SDMX data API base URL (overrides config key `sdmx_base`)
+    #[arg(long, env = "RIP_SDMX_BASE")]
+    sdmx_base: Option<String>,
+
+    /// IMF SDMX structure API base URL (overrides config key `sdmx_structure_base`)
+    #[arg(long, env = "RIP_SDMX_STRUCTURE_BASE")]
+    sdmx_structure_base: Option<String>,
+
+    /// IMF DataMapper API base URL (overrides config key `datamapper_base`)
+    #[arg(long, env = "RIP_DATAMAPPER_BASE")]
+    datamapper_base: Option<String>,
+
+    /// HTTP connect timeout, e.g. 10s (overrides config key `http.connect_timeout`)
+    #[arg(long, env = "RIP_CONNECT_TIMEOUT")]
+    connect_timeout: Option<String>,
+
+    /// HTTP read timeout, e.g. 30s (overrides config key `http.read_timeout`)
+    #[arg(long, env = "RIP_READ_TIMEOUT")]
+    read_timeout: Option<String>,
+
+    /// Total attempts per HTTP request, retries included (overrides config key `http.max_attempts`)
+    #[arg(long, env = "RIP_MAX_ATTEMPTS")]
+    max_attempts: Option<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve recorded SDMX-ML and DataMapper JSON fixtures over HTTP (mock IMF server)
+    ServeFixtures {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8787")]
+        listen: String,
+
+        /// Directory with extra/override fixtures (same layout as the built-in set)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+
+        /// Answer with this HTTP status instead of the fixture (e.g. 503 or 429), to test retries
+        #[arg(long)]
+        fail_status: Option<u16>,
+
+        /// Only the first N requests to each path misbehave (0 = every request)
+        #[arg(long, default_value_t = 0)]
+        fail_times: u32,
+
+        /// Retry-After seconds sent with --fail-status responses
+        #[arg(long)]
+        retry_after: Option<u64>,
+
+        /// Wait this long (e.g. 45s) before answering misbehaving requests, to test read timeouts
+        #[arg(long)]
+        delay: Option<String>,
+    },
+
+    /// Inspect and maintain the on-disk cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    /// Export/import offline data bundles for air-gapped machines
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleAction {
+    /// Pack cached codelists and series (of the chosen countries) into one archive
+    Export {
+        /// Country code to include (repeatable; default: every cached series)
+        #[arg(long = "country")]
+        countries: Vec<String>,
+
+        /// Output file
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Load a bundle into the local cache
+    Import {
+        /// Bundle file
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// List entries with source URL, fetch time and size
+    Ls,
+    /// Print one entry's contents
+    Show {
+        /// Entry name as shown by `rip cache ls`
+        entry: String,
+    },
+    /// Re-parse every entry and flag corrupt files
+    Verify {
+        /// Delete entries that fail to parse
+        #[arg(long, default_value_t = false)]
+        evict: bool,
+    },
+    /// Delete entries fetched longer ago than the given age
+    Prune {
+        /// Age such as 12h, 30d or 2w
+        #[arg(long)]
+        older_than: String,
+    },
+    /// Delete everything in the cache
+    Clear {
+        /// Do not ask for confirmation
+        #[arg(long, short, default_value_t = false)]
+        yes: bool,
+    },
 }
 
-// ----------------------- Shared Types -----------------------
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Item {
-    code: String,
-    name: String,
+// ----------------------- Run settings -----------------------
+struct RunContext<'a> {
+    theme: &'a ColorfulTheme,
+    verbose: bool,
+    no_jokes: bool,
+    format: Format,
+    direction: Direction,
+    prorate: bool,
+    convention: YearConvention,
+    /// Frequency of the SDMX series (monthly in the other modes).
+    frequency: Frequency,
+    /// A `--dataflow`/`--key` series replaces the CPI.
+    custom_series: bool,
+    series: bool,
+    plot: Option<PlotStyle>,
+    chart: Option<PathBuf>,
+    compare: Vec<String>,
+    report: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
-struct YearInflation {
-    year: i32,
-    pct: f64,
+struct RaiseQuery {
+    country: Option<String>,
+    old: (String, f64),
+    new: (String, f64),
+    base: Option<String>,
+}
+
+struct HistoryQuery {
+    country: Option<String>,
+    records: Vec<HistoryRecord>,
+    base: Option<String>,
+}
+
+/// SDMX series selected by `--index-type`/`--frequency`, or by `--dataflow`/`--key`.
+struct SdmxSeries {
+    index_type: String,
+    frequency: Frequency,
+    custom: Option<SeriesKey>,
+}
+
+struct DecomposeQuery {
+    country: Option<String>,
+    start_input: String,
+    end_input: Option<String>,
+}
+
+struct Query {
+    country: Option<String>,
+    start_input: String,
+    end_input: Option<String>,
+    amount: f64,
+    current: Option<f64>,
+    base: Option<String>,
+    /// COICOP divisions (empty: all items; several: side by side).
+    coicop: Vec<Item>,
 }
 
 // ----------------------- Main -----------------------
@@ -108,16 +481,63 @@ async fn main() -> Result<()> {
     let args = Args::parse();
     let theme = ColorfulTheme::default();
 
-    let sdmx_client = Client::builder()
-        .user_agent("rip/0.3.1 (rust reqwest)")
-        .build()
-        .context("Failed to build SDMX HTTP client")?;
+    if let Some(Command::ServeFixtures {
+        listen,
+        dir,
+        fail_status,
+        fail_times,
+        retry_after,
+        delay,
+    }) = args.command
+    {
+        let faults = Faults {
+            status: fail_status,
+            times: fail_times,
+            retry_after,
+            delay: delay.as_deref().map(parse_duration).transpose().context("--delay")?,
+        };
+        return serve_fixtures(&listen, dir, faults, args.verbose).await;
+    }
+
+    let config = Config::load(args.config.as_deref())?;
+    let endpoints = Endpoints::default().with_config(&config).with_overrides(
+        args.sdmx_base.as_deref(),
+        args.sdmx_structure_base.as_deref(),
+        args.datamapper_base.as_deref(),
+    );
+
+    let cache_policy = if args.offline {
+        CachePolicy::Offline
+    } else {
+        args.cache.or(config.cache.policy).unwrap_or_default()
+    };
+    let cache = Cache::new(default_cache_dir()?, cache_policy, config.cache.ttls()?);
+
+    let mut http = config.http.settings()?;
+    if let Some(s) = &args.connect_timeout {
+        http.connect_timeout = parse_duration(s).context("--connect-timeout")?;
+    }
+    if let Some(s) = &args.read_timeout {
+        http.read_timeout = parse_duration(s).context("--read-timeout")?;
+    }
+    if let Some(n) = args.max_attempts {
+        http.max_attempts = n;
+    }
 
-    let datamapper_client = build_datamapper_client()?;
+    match args.command {
+        Some(Command::Cache { action }) => return run_cache(&cache, &theme, action),
+        Some(Command::Bundle { action }) => return run_bundle(&cache, action),
+        _ => {}
+    }
 
-    let cache_dir = default_cache_dir()?;
-    if args.cache {
-        fs::create_dir_all(&cache_dir).ok();
+    if args.format == Format::Csv && !args.series && args.history.is_none() {
+        return Err(anyhow!("--format csv is only available with --series or --history"));
+    }
+    if !args.compare.is_empty() && args.chart.is_none() && args.report.is_none() {
+        return Err(anyhow!("--compare needs --chart or --report"));
+    }
+    if args.plot.is_some() && args.format != Format::Text {
+        return Err(anyhow!("--plot is only available with --format text"));
     }
 
     // 1) Mode dropdown
@@ -126,18 +546,196 @@ async fn main() -> Result<()> {
         None => prompt_mode(&theme)?,
     };
 
+    if mode != Mode::Datamapper {
+        if args.prorate {
+            return Err(anyhow!("--prorate is only available in DataMapper mode"));
+        }
+        if args.year_convention.is_some() {
+            return Err(anyhow!("--year-convention is only available in DataMapper mode"));
+        }
+    } else if args.base.is_some() && (args.prorate || args.year_convention.is_some()) {
+        // The base period may widen the fetched range, which would move the
+        // boundary years the convention applies to.
+        return Err(anyhow!("--base cannot be combined with --prorate or --year-convention"));
+    }
+
+    let coicop = match args.coicop {
+        None => Vec::new(),
+        // Hybrid fills gaps with headline PCPIPCH, which would not match a division.
+        Some(_) if mode != Mode::Sdmx => return Err(anyhow!("--coicop is only available in SDMX mode")),
+        Some(codes) if codes.is_empty() => {
+            let provider = SdmxProvider::new(sdmx::build_client(http)?, cache.clone()).with_endpoints(&endpoints);
+            prompt_coicop(&theme, &provider.load_or_fetch_coicop().await?)?
+        }
+        Some(codes) => {
+            let mut items: Vec<Item> = Vec::new();
+            for code in codes.iter().map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()) {
+                if !items.iter().any(|x| x.code == code) {
+                    items.push(Item { code: code.clone(), name: code }); // name fallback = code
+                }
+            }
+            items
+        }
+    };
+    if mode != Mode::Sdmx && (args.index_type.is_some() || args.frequency.is_some()) {
+        // Hybrid chains headline PCPIPCH into monthly CPI levels: only the default series matches.
+        return Err(anyhow!("--index-type and --frequency are only available in SDMX mode"));
+    }
+    let index_type = args
+        .index_type
+        .as_deref()
+        .map(|t| t.trim().to_uppercase())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| SDMX_CPI_INDEX_TYPE.to_string());
+    // Generic series: resolved against the dataflow's DSD before any data request.
+    let custom = match (&args.dataflow, &args.key) {
+        (Some(_), Some(_)) if mode != Mode::Sdmx => {
+            return Err(anyhow!("--dataflow and --key are only available in SDMX mode"))
+        }
+        (Some(dataflow), Some(key)) => {
+            let dataflow = dataflow.trim().to_uppercase();
+            let provider = SdmxProvider::new(sdmx::build_client(http)?, cache.clone()).with_endpoints(&endpoints);
+            let structure = provider
+                .load_or_fetch_structure(&dataflow)
+                .await
+                .with_context(|| format!("Cannot load the structure of dataflow {}", dataflow))?;
+            Some(
+                structure
+                    .resolve_key(&dataflow, key)
+                    .with_context(|| format!("Invalid --key for dataflow {}", dataflow))?,
+            )
+        }
+        _ => None,
+    };
+    let frequency = match &custom {
+        Some(series) => series.frequency()?,
+        None => args.frequency.unwrap_or_default(),
+    };
+    // The series key already names the area: it stands in for --country.
+    let country = match &custom {
+        Some(series) => Some(series.country().map_or_else(|| series.key(), str::to_string)),
+        None => args.country,
+    };
+    let sdmx_series = SdmxSeries {
+        index_type,
+        frequency,
+        custom,
+    };
+    if frequency != Frequency::Monthly && args.basket.is_some() {
+        return Err(anyhow!("--basket needs monthly series (the index is chain-linked every December)"));
+    }
+    if args.decompose && mode != Mode::Sdmx {
+        return Err(anyhow!("--decompose is only available in SDMX mode"));
+    }
+    let basket = match &args.basket {
+        Some(_) if mode != Mode::Sdmx => return Err(anyhow!("--basket is only available in SDMX mode")),
+        Some(path) => Some(read_basket(path)?),
+        None => None,
+    };
+    if coicop.len() > 1 {
+        if args.series || args.plot.is_some() || args.chart.is_some() || args.report.is_some() {
+            return Err(anyhow!("Several --coicop divisions cannot be combined with --series, --plot, --chart or --report"));
+        }
+        if args.current.is_some() || args.format == Format::Csv {
+            return Err(anyhow!("Several --coicop divisions cannot be combined with --current or --format csv"));
+        }
+    }
+
+    let ctx = RunContext {
+        theme: &theme,
+        verbose: args.verbose,
+        no_jokes: args.no_jokes,
+        format: args.format,
+        direction: args.direction,
+        prorate: args.prorate,
+        convention: args.year_convention.unwrap_or_default(),
+        frequency,
+        custom_series: sdmx_series.custom.is_some(),
+        series: args.series,
+        plot: args.plot,
+        chart: args.chart.clone(),
+        compare: args.compare.clone(),
+        report: args.report.clone(),
+    };
+
+    // Real raise calculator: two dated amounts instead of --start/--amount.
+    if !args.raise.is_empty() {
+        let query = RaiseQuery {
+            country,
+            old: parse_dated_amount(&args.raise[0]).context("--raise OLD")?,
+            new: parse_dated_amount(&args.raise[1]).context("--raise NEW")?,
+            base: args.base,
+        };
+        return match mode {
+            Mode::Sdmx => {
+                let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+                run_raise(&provider, Mode::Sdmx, &ctx, query).await
+            }
+            Mode::Datamapper => {
+                let provider =
+                    DataMapperProvider::new(datamapper::build_client(http)?, cache).with_endpoints(&endpoints);
+                run_raise(&provider, Mode::Datamapper, &ctx, query).await
+            }
+            Mode::Hybrid => {
+                let provider = hybrid_provider(http, cache, &endpoints)?;
+                run_raise(&provider, Mode::Hybrid, &ctx, query).await
+            }
+        };
+    }
+
+    // Salary history: one series fetch for every record of the file.
+    if let Some(path) = &args.history {
+        let query = HistoryQuery {
+            country,
+            records: read_history(path)?,
+            base: args.base,
+        };
+        return match mode {
+            Mode::Sdmx => {
+                let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+                run_history(&provider, Mode::Sdmx, &ctx, query).await
+            }
+            Mode::Datamapper => {
+                let provider =
+                    DataMapperProvider::new(datamapper::build_client(http)?, cache).with_endpoints(&endpoints);
+                run_history(&provider, Mode::Datamapper, &ctx, query).await
+            }
+            Mode::Hybrid => {
+                let provider = hybrid_provider(http, cache, &endpoints)?;
+                run_history(&provider, Mode::Hybrid, &ctx, query).await
+            }
+        };
+    }
+
+    // Inflation decomposition: no amount involved.
+    if args.decompose {
+        let query = DecomposeQuery {
+            country,
+            start_input: match args.start {
+                Some(s) => s.trim().to_string(),
+                None => prompt_start_monthly(&theme)?,
+            },
+            end_input: args.end.map(|e| e.trim().to_string()).filter(|e| !e.is_empty()),
+        };
+        let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+        return run_decompose(&provider, &ctx, query).await;
+    }
+
     // 2) Amount
     let amount = match args.amount {
         Some(a) if a > 0.0 => a,
         Some(_) => return Err(anyhow!("Amount must be > 0")),
         None => prompt_amount(&theme)?,
     };
+    if args.current.is_some_and(|c| c <= 0.0) {
+        return Err(anyhow!("Current amount must be > 0"));
+    }
 
     // 3) Start
     let start_input = match args.start.clone() {
         Some(s) => s.trim().to_string(),
         None => match mode {
-            Mode::Sdmx => prompt_start_monthly(&theme)?,
+            Mode::Sdmx | Mode::Hybrid => prompt_start_monthly(&theme)?,
             Mode::Datamapper => prompt_start_yearly(&theme)?,
         },
     };
@@ -151,47 +749,214 @@ async fn main() -> Result<()> {
         }
     });
 
+    let query = Query {
+        country,
+        start_input,
+        end_input,
+        amount,
+        current: args.current,
+        base: args.base,
+        coicop,
+    };
+
+    // Personal basket: one series per division, plus headline CPI.
+    if let Some(basket) = basket {
+        let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+        return run_basket(&provider, &ctx, query, basket).await;
+    }
+
     match mode {
+        Mode::Sdmx if query.coicop.len() > 1 => {
+            let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+            run_components(&provider, &ctx, query).await?;
+        }
         Mode::Sdmx => {
-            run_sdmx(
-                &sdmx_client,
-                &cache_dir,
-                args.cache,
-                args.verbose,
-                &theme,
-                args.country,
-                start_input,
-                amount,
-                args.no_jokes,
-                end_input.clone(),
-            )
-            .await?;
+            let provider = sdmx_provider(http, cache, &endpoints, &sdmx_series)?;
+            let provider = match query.coicop.first() {
+                Some(division) => provider.with_coicop(&division.code),
+                None => provider,
+            };
+            run_sdmx(&provider, &provider, Mode::Sdmx, &ctx, query).await?;
         }
         Mode::Datamapper => {
-            run_datamapper(
-                &datamapper_client,
-                &cache_dir,
-                args.cache,
-                args.verbose,
-                &theme,
-                args.country,
-                start_input,
-                amount,
-                args.no_jokes,
-                end_input.clone(),
-            )
-            .await?;
+            let provider = DataMapperProvider::new(datamapper::build_client(http)?, cache)
+                .with_endpoints(&endpoints)
+                .with_prorating(ctx.prorate)
+                .with_convention(ctx.convention);
+            run_datamapper(&provider, &ctx, query).await?;
+        }
+        Mode::Hybrid => {
+            let provider = hybrid_provider(http, cache, &endpoints)?;
+            run_sdmx(&provider, provider.sdmx(), Mode::Hybrid, &ctx, query).await?;
         }
     }
 
     Ok(())
 }
 
+/// SDMX provider for the selected series.
+fn sdmx_provider(http: HttpSettings, cache: Cache, endpoints: &Endpoints, series: &SdmxSeries) -> Result<SdmxProvider> {
+    let provider = SdmxProvider::new(sdmx::build_client(http)?, cache)
+        .with_endpoints(endpoints)
+        .with_index_type(&series.index_type)
+        .with_frequency(series.frequency);
+    match &series.custom {
+        Some(key) => provider.with_series(key),
+        None => Ok(provider),
+    }
+}
+
+/// SDMX levels, with DataMapper rates chained in where SDMX has no data.
+fn hybrid_provider(http: HttpSettings, cache: Cache, endpoints: &Endpoints) -> Result<HybridProvider> {
+    let sdmx = SdmxProvider::new(sdmx::build_client(http)?, cache.clone()).with_endpoints(endpoints);
+    let dm = DataMapperProvider::new(datamapper::build_client(http)?, cache).with_endpoints(endpoints);
+    Ok(HybridProvider::new(sdmx, dm))
+}
+
+// ----------------------- Fixture server -----------------------
+async fn serve_fixtures(listen: &str, dir: Option<PathBuf>, faults: Faults, verbose: bool) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Cannot listen on {}", listen))?;
+    let addr = listener.local_addr()?.to_string();
+    let endpoints = Endpoints::fixture_server(&addr);
+
+    println!("Serving IMF fixtures on http://{}", addr);
+    println!("Point RIP at it with:");
+    println!("  export RIP_SDMX_BASE={}", endpoints.sdmx);
+    println!("  export RIP_SDMX_STRUCTURE_BASE={}", endpoints.sdmx_structure);
+    println!("  export RIP_DATAMAPPER_BASE={}", endpoints.datamapper);
+
+    if faults.is_active() {
+        println!("Injecting faults: {:?}", faults);
+    }
+
+    FixtureServer::new(dir)
+        .with_faults(faults)
+        .with_verbose(verbose)
+        .serve(listener)
+        .await
+}
+
+// ----------------------- Cache subcommand -----------------------
+fn run_cache(cache: &Cache, theme: &ColorfulTheme, action: CacheAction) -> Result<()> {
+    match action {
+        CacheAction::Ls => {
+            let entries = cache.entries()?;
+            println!("Cache dir: {}", cache.dir().display());
+            if entries.is_empty() {
+                println!("(empty)");
+                return Ok(());
+            }
+            for e in &entries {
+                println!(
+                    "{:<48} {:<14} {:>10}  {:<20}  {}",
+                    e.name,
+                    e.kind.to_string(),
+                    fmt_size(e.size),
+                    e.fetched_at
+                        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                        .unwrap_or_else(|| "-".into()),
+                    e.source_url.as_deref().unwrap_or("-")
+                );
+            }
+            let total: u64 = entries.iter().map(|e| e.size).sum();
+            println!("{} entries, {}", entries.len(), fmt_size(total));
+        }
+        CacheAction::Show { entry } => {
+            let e = cache.entry(&entry)?;
+            let b = fs::read(&e.path).with_context(|| format!("Cannot read {}", e.path.display()))?;
+            println!("{}", String::from_utf8_lossy(&b));
+        }
+        CacheAction::Verify { evict } => {
+            let mut bad = 0;
+            for e in cache.entries()? {
+                match inventory::verify(&e) {
+                    Ok(true) => println!("ok       {}", e.name),
+                    Ok(false) => println!("skipped  {} ({})", e.name, e.kind),
+                    Err(err) => {
+                        bad += 1;
+                        println!("CORRUPT  {}: {:#}", e.name, err);
+                        if evict {
+                            cache.remove(&e)?;
+                            println!("         evicted");
+                        }
+                    }
+                }
+            }
+            if bad > 0 && !evict {
+                return Err(anyhow!("{} corrupt cache entries (re-run with --evict to delete them)", bad));
+            }
+        }
+        CacheAction::Prune { older_than } => {
+            let max_age = chrono::Duration::from_std(parse_duration(&older_than)?)?;
+            let cutoff = chrono::Utc::now() - max_age;
+            let mut removed = 0;
+            for e in cache.entries()? {
+                if e.fetched_at.is_some_and(|t| t < cutoff) {
+                    cache.remove(&e)?;
+                    println!("removed  {}", e.name);
+                    removed += 1;
+                }
+            }
+            println!("Pruned {} entries older than {}", removed, older_than);
+        }
+        CacheAction::Clear { yes } => {
+            let confirmed = yes
+                || Confirm::with_theme(theme)
+                    .with_prompt(format!("Delete everything in {}?", cache.dir().display()))
+                    .default(false)
+                    .interact()?;
+            if confirmed {
+                println!("Removed {} entries", cache.clear()?);
+            }
+        }
+    }
+    Ok(())
+}
+
+// ----------------------- Bundle subcommand -----------------------
+fn run_bundle(cache: &Cache, action: BundleAction) -> Result<()> {
+    match action {
+        BundleAction::Export { countries, output } => {
+            let b = bundle::export(cache, &countries)?;
+            let included = bundle::bundle_countries(&b);
+            for c in &countries {
+                if !included.iter().any(|x| x.eq_ignore_ascii_case(c)) {
+                    eprintln!("Warning: no cached series for {} (run RIP online for it first)", c.to_uppercase());
+                }
+            }
+            bundle::write_bundle(&b, &output)?;
+            println!(
+                "Wrote {} ({} entries, countries: {})",
+                output.display(),
+                b.entries.len(),
+                if included.is_empty() { "-".to_string() } else { included.join(", ") }
+            );
+        }
+        BundleAction::Import { file } => {
+            let b = bundle::read_bundle(&file)?;
+            let n = bundle::import(cache, &b)?;
+            println!(
+                "Imported {} entries from {} (bundle v{}, created {}, countries: {})",
+                n,
+                file.display(),
+                b.version,
+                b.created_at.format("%Y-%m-%d %H:%M UTC"),
+                bundle::bundle_countries(&b).join(", ")
+            );
+            println!("Run with --offline to use only this data.");
+        }
+    }
+    Ok(())
+}
+
 // ----------------------- Prompts -----------------------
 fn prompt_mode(theme: &ColorfulTheme) -> Result<Mode> {
     let items = vec![
         "SDMX (recommended): Monthly CPI index level (most precise)",
         "DataMapper: Annual inflation approximation (PCPIPCH)",
+        "Hybrid: Monthly CPI, gaps filled from annual inflation (PCPIPCH)",
     ];
 
     let idx = Select::with_theme(theme)
@@ -201,7 +966,29 @@ fn prompt_mode(theme: &ColorfulTheme) -> Result<Mode> {
         .interact()
         .context("Mode selection failed")?;
 
-    Ok(if idx == 0 { Mode::Sdmx } else { Mode::Datamapper })
+    Ok(match idx {
+        0 => Mode::Sdmx,
+        1 => Mode::Datamapper,
+        _ => Mode::Hybrid,
+    })
+}
+
+fn prompt_coicop(theme: &ColorfulTheme, divisions: &[Item]) -> Result<Vec<Item>> {
+    let labels: Vec<String> = divisions
+        .iter()
+        .map(|x| format!("{} - {}", x.code, x.name))
+        .collect();
+
+    let picked = MultiSelect::with_theme(theme)
+        .with_prompt("Select COICOP divisions (space to toggle, enter to confirm)")
+        .items(&labels)
+        .interact()
+        .context("COICOP selection failed")?;
+    if picked.is_empty() {
+        return Err(anyhow!("No COICOP division selected"));
+    }
+
+    Ok(picked.into_iter().map(|i| divisions[i].clone()).collect())
 }
 
 fn prompt_amount(theme: &ColorfulTheme) -> Result<f64> {
@@ -266,49 +1053,87 @@ fn prompt_fuzzy_pick(theme: &ColorfulTheme, prompt: &str, items: &[Item]) -> Res
     Ok((it.code.clone(), it.name.clone()))
 }
 
-// ----------------------- Parsing helpers -----------------------
-fn parse_ym(s: &str) -> Result<String> {
-    let t = s.trim();
-    if t.len() != 7 {
-        return Err(anyhow!("Expected YYYY-MM"));
-    }
-    let parts: Vec<&str> = t.split('-').collect();
-    if parts.len() != 2 {
-        return Err(anyhow!("Expected YYYY-MM"));
-    }
-    let y: i32 = parts[0].parse()?;
-    let m: u32 = parts[1].parse()?;
-    if !(1..=12).contains(&m) {
-        return Err(anyhow!("Month out of range"));
+/// `--base` as a period of the mode's format (of `freq` for SDMX), with the
+/// range covering it.
+fn base_range(mode: Mode, freq: Frequency, base: &str) -> Result<(String, PeriodRange)> {
+    match mode {
+        Mode::Sdmx | Mode::Hybrid => {
+            let p = freq.convert(base, false).context("--base must be YYYY-MM, YYYY-Qn or YYYY")?;
+            let range = PeriodRange::with_frequency(&p, Some(&p), freq).context("--base")?;
+            Ok((p, range))
+        }
+        Mode::Datamapper => {
+            let y = parse_year_loose(base).context("--base must be YYYY (or YYYY-MM)")?;
+            let range = PeriodRange::yearly(&y.to_string(), Some(&y.to_string())).context("--base")?;
+            Ok((format!("{:04}", y), range))
+        }
     }
-    let _ = NaiveDate::from_ymd_opt(y, m, 1).ok_or_else(|| anyhow!("Invalid date"))?;
-    Ok(format!("{:04}-{:02}", y, m))
 }
 
-fn parse_year_loose(s: &str) -> Result<i32> {
-    let t = s.trim();
-    let year_part = t.split('-').next().unwrap_or(t);
-    let y: i32 = year_part.parse()?;
-    if !(1800..=3000).contains(&y) {
-        return Err(anyhow!("Year out of reasonable range"));
-    }
-    Ok(y)
+/// Series over `range`, plus the `--base` point when one is given.
+async fn fetch_series_and_base(
+    provider: &dyn CpiProvider,
+    mode: Mode,
+    country: &str,
+    range: &PeriodRange,
+    base: Option<&str>,
+) -> Result<(PriceIndexSeries, Option<IndexPoint>)> {
+    // SDMX ranges are in the periods of the series' frequency.
+    let freq = Frequency::of(&range.start).unwrap_or_default();
+    let base = base.map(|b| base_range(mode, freq, b)).transpose()?;
+    fetch_with_base(provider, country, range, base.as_ref().map(|(p, r)| (p.as_str(), r))).await
 }
 
-// NEW: convert "YYYY-MM" => "YYYY-MMM" (SDMX monthly format "YYYY-M01")
-fn ym_to_sdmx_period(ym: &str) -> Result<String> {
-    let t = parse_ym(ym)?;
-    let parts: Vec<&str> = t.split('-').collect();
-    let y: i32 = parts[0].parse()?;
-    let m: u32 = parts[1].parse()?;
-    Ok(format!("{:04}-M{:02}", y, m))
+/// `DATE:AMOUNT`, e.g. "2020-03:40000" (the date is checked by the runner).
+fn parse_dated_amount(s: &str) -> Result<(String, f64)> {
+    let (date, amount) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Expected DATE:AMOUNT, e.g. 2020-03:40000 (got '{}')", s))?;
+    let amount: f64 = amount.trim().parse().with_context(|| format!("Invalid amount '{}'", amount.trim()))?;
+    if amount <= 0.0 {
+        return Err(anyhow!("Amount must be > 0"));
+    }
+    Ok((date.trim().to_string(), amount))
 }
 
-// ----------------------- Cache dir -----------------------
-fn default_cache_dir() -> Result<PathBuf> {
-    let mut dir = dirs::cache_dir().ok_or_else(|| anyhow!("Could not locate a cache directory"))?;
-    dir.push("rip");
-    Ok(dir)
+/// Country code and name from `--country`, or picked from the provider's list.
+async fn select_country(
+    provider: &dyn CpiProvider,
+    mode: Mode,
+    ctx: &RunContext<'_>,
+    country: Option<String>,
+) -> Result<(String, String)> {
+    match mode {
+        // If user passed --country, don't depend on any metadata/codelist endpoint.
+        // Otherwise load ISO3 country list from SDMX Central and show fuzzy picker.
+        Mode::Sdmx | Mode::Hybrid => match country {
+            Some(code) => {
+                let code_up = code.trim().to_uppercase();
+                Ok((code_up.clone(), code_up)) // name fallback = code
+            }
+            None => {
+                let countries = provider.list_countries().await?;
+                prompt_fuzzy_pick(ctx.theme, "Select country (SDMX ISO3)", &countries)
+            }
+        },
+        Mode::Datamapper => {
+            let countries = provider.list_countries().await?;
+            match country {
+                Some(code) => {
+                    let code_up = code.trim().to_uppercase();
+                    let name = countries
+                        .iter()
+                        .find(|x| x.code == code_up)
+                        .map(|x| x.name.clone())
+                        .ok_or_else(|| {
+                            anyhow!("Country code '{}' not found in DataMapper countries list", code_up)
+                        })?;
+                    Ok((code_up, name))
+                }
+                None => prompt_fuzzy_pick(ctx.theme, "Select country (DataMapper ISO3)", &countries),
+            }
+        }
+    }
 }
 
 // ----------------------- Formatting & Report -----------------------
@@ -328,48 +1153,459 @@ fn fmt_money(x: f64) -> String {
     )
 }
 
-fn sdmx_period_to_ym(p: &str) -> String {
-    // "2025-M11" -> "2025-11"
-    // If parsing fails, return original string.
-    if p.len() == 8 && p.as_bytes()[4] == b'-' && p.as_bytes()[5] == b'M' {
-        let year = &p[0..4];
-        let mm = &p[6..8];
-        if mm.chars().all(|c| c.is_ascii_digit()) {
-            return format!("{}-{}", year, mm);
-        }
+fn fmt_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut v = bytes as f64;
+    let mut unit = 0;
+    while v >= 1024.0 && unit < UNITS.len() - 1 {
+        v /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", v, UNITS[unit])
+    }
+}
+
+/// "Italy (ITA)", or just the code when no name is known.
+fn country_label(name: &str, code: &str) -> String {
+    if name == code {
+        code.to_string()
+    } else {
+        format!("{} ({})", name, code)
     }
-    p.to_string()
 }
 
-fn print_header(
+fn header_fields(
     mode: Mode,
     country_name: &str,
     source_label: &str,
     indicator: &str,
     start_label: &str,
     latest_label: &str,
-) {
+) -> Vec<(String, String)> {
+    [
+        ("Mode", format!("{:?}", mode)),
+        ("Country", country_name.to_string()),
+        ("Source", source_label.to_string()),
+        ("Indicator", indicator.to_string()),
+        ("Start", start_label.to_string()),
+        ("Latest", latest_label.to_string()),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect()
+}
+
+/// Header value of `--base`. Amounts are valued with one definition
+/// everywhere (`amount_at_D in P prices = amount * level_P / level_D`):
+/// deflating, the base is the date the amount is valued at, in start
+/// prices; inflating (and in `--history`), it is the price period.
+fn base_label(direction: Direction, base: &str, start: &str) -> String {
+    match direction {
+        Direction::Deflate => format!("{} (real value at this period, in {} prices)", base, start),
+        Direction::Inflate => format!("{} prices", base),
+    }
+}
+
+/// "CP01 (Food and non-alcoholic beverages)", or the bare code when its name is unknown.
+fn division_label(division: &Item) -> String {
+    if division.name == division.code {
+        division.code.clone()
+    } else {
+        format!("{} ({})", division.code, division.name)
+    }
+}
+
+/// Caption of the CPI levels table of the monthly modes.
+fn levels_caption(mode: Mode, ctx: &RunContext<'_>) -> &'static str {
+    match mode {
+        Mode::Hybrid => "CPI index levels used (SDMX + DataMapper)",
+        _ if ctx.custom_series => "Index levels used (SDMX)",
+        _ => "CPI index levels used (SDMX)",
+    }
+}
+
+/// Segments of a hybrid series (`None` when it has a single source).
+fn segments(series: &PriceIndexSeries) -> Option<Vec<SeriesSegment>> {
+    (!series.segments.is_empty()).then(|| series.segments.clone())
+}
+
+/// "START → END: source" per segment of a hybrid series.
+fn source_lines(series: &PriceIndexSeries) -> Vec<String> {
+    series
+        .segments
+        .iter()
+        .map(|s| format!("{} → {}: {}", s.start, s.end, s.source))
+        .collect()
+}
+
+fn print_sources(series: &PriceIndexSeries) {
+    let lines = source_lines(series);
+    if lines.is_empty() {
+        return;
+    }
+    println!("Sources:");
+    for l in lines {
+        println!("  {}", l);
+    }
+}
+
+fn print_header(fields: &[(String, String)]) {
     println!("================= Real Income (Inflation-Adjusted) =================");
-    println!("Mode: {:?}", mode);
-    println!("Country: {}", country_name);
-    println!("Source: {}", source_label);
-    println!("Indicator: {}", indicator);
-    println!("Start: {}", start_label);
-    println!("Latest: {}", latest_label);
+    for (k, v) in fields {
+        println!("{}: {}", k, v);
+    }
     println!("=====================================================================");
 }
 
-fn print_results(nominal: f64, real_now: f64, loss: f64, loss_pct: f64) {
-    println!("Nominal amount: {}", fmt_money(nominal));
-    println!("Real value now: {}", fmt_money(real_now));
-    println!("Purchasing-power loss: {} ({:.2}%)", fmt_money(loss), loss_pct);
+fn result_fields(
+    r: &RealValue,
+    inflated: Option<&InflatedValue>,
+    raise: Option<&RaiseNeeded>,
+    start_label: &str,
+    target: &str,
+    based: bool,
+) -> Vec<(String, String)> {
+    let mut fields = match inflated {
+        None => vec![
+            ("Nominal amount".to_string(), fmt_money(r.nominal)),
+            (
+                if based {
+                    format!("Real value at {}, in {} prices", target, start_label)
+                } else {
+                    "Real value now".to_string()
+                },
+                fmt_money(r.real),
+            ),
+            (
+                "Purchasing-power loss".to_string(),
+                format!("{} ({:.2}%)", fmt_money(r.loss), r.loss_pct),
+            ),
+        ],
+        Some(i) => vec![
+            (format!("Amount in {} prices", start_label), fmt_money(i.nominal)),
+            (format!("Equivalent in {} prices", target), fmt_money(i.equivalent)),
+            (
+                "Price increase".to_string(),
+                format!("{} ({:+.2}%)", fmt_money(i.increase), i.increase_pct),
+            ),
+        ],
+    };
+    if let Some(x) = raise {
+        fields.push(("Current amount".to_string(), fmt_money(x.current)));
+        fields.push(if x.raise > 0.0 {
+            (
+                "Raise needed to break even".to_string(),
+                format!("{} ({:+.2}%)", fmt_money(x.raise), x.raise_pct),
+            )
+        } else {
+            (
+                "Ahead of inflation by".to_string(),
+                format!("{} ({:.2}%)", fmt_money(-x.raise), -x.raise_pct),
+            )
+        });
+    }
+    fields
+}
+
+fn print_results(fields: &[(String, String)]) {
+    for (k, v) in fields {
+        println!("{}: {}", k, v);
+    }
+}
+
+/// Adjusted amount of a series row for `direction`.
+fn point_value(p: &RealValuePoint, direction: Direction) -> f64 {
+    match direction {
+        Direction::Deflate => p.real,
+        Direction::Inflate => p.equivalent,
+    }
+}
+
+fn print_series_table(points: &[RealValuePoint], level_label: &str, direction: Direction) {
+    let pct = |v: Option<f64>| v.map(|x| format!("{:+.2}%", x)).unwrap_or_else(|| "-".into());
+    println!(
+        "{:<8} {:>10} {:>8} {:>8} {:>10} {:>16}",
+        "Period",
+        level_label,
+        "MoM",
+        "YoY",
+        "Cumul.",
+        direction.value_label()
+    );
+    for p in points {
+        println!(
+            "{:<8} {:>10.4} {:>8} {:>8} {:>10} {:>16}",
+            p.period,
+            p.level,
+            pct(p.mom_pct),
+            pct(p.yoy_pct),
+            format!("{:+.2}%", p.cumulative_pct),
+            fmt_money(point_value(p, direction))
+        );
+    }
+}
+
+/// Terminal width for charts: the real one, else `$COLUMNS`, else 80.
+fn terminal_width() -> usize {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, cols)| cols as usize)
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
+
+fn print_plots(
+    style: PlotStyle,
+    series: &PriceIndexSeries,
+    nominal: f64,
+    direction: Direction,
+    level_label: &str,
+    step: &str,
+) {
+    let levels: Vec<(String, f64)> = series.points.iter().map(|p| (p.period.clone(), p.level)).collect();
+    let value_label = direction.value_label();
+    let real = adjusted_values(series, nominal, direction);
+    let width = terminal_width();
+
+    match style {
+        PlotStyle::Line => {
+            println!("{} by {}:", level_label, step);
+            print!("{}", plot::line_chart(level_label, &levels, width, 10));
+            println!();
+            println!("{} of {} by {}:", value_label, fmt_money(nominal), step);
+            print!("{}", plot::line_chart(value_label, &real, width, 10));
+        }
+        PlotStyle::Spark => {
+            let (Some(first), Some(last)) = (levels.first(), levels.last()) else {
+                return;
+            };
+            let label_w = level_label.chars().count().max(value_label.len());
+            let room = width.saturating_sub(label_w + first.0.len() + last.0.len() + 30).max(10);
+            let spark = |label: &str, pts: &[(String, f64)], fmt: &dyn Fn(f64) -> String| {
+                let values: Vec<f64> = pts.iter().map(|(_, v)| *v).collect();
+                println!(
+                    "{:<w$}  {} {} {}  {} → {}",
+                    label,
+                    first.0,
+                    plot::sparkline(&values, room),
+                    last.0,
+                    fmt(values[0]),
+                    fmt(values[values.len() - 1]),
+                    w = label_w
+                );
+            };
+            spark(level_label, &levels, &|v| format!("{:.2}", v));
+            spark(value_label, &real, &fmt_money);
+        }
+    }
+}
+
+fn series_report_table(
+    caption: &str,
+    level_label: &str,
+    points: &[RealValuePoint],
+    direction: Direction,
+) -> ReportTable {
+    let pct = |v: Option<f64>| v.map(|x| format!("{:+.2}%", x)).unwrap_or_else(|| "-".into());
+    ReportTable {
+        caption: caption.to_string(),
+        columns: ["Period", level_label, "MoM", "YoY", "Cumulative", direction.value_label()]
+            .iter()
+            .map(|c| c.to_string())
+            .collect(),
+        rows: points
+            .iter()
+            .map(|p| {
+                vec![
+                    p.period.clone(),
+                    format!("{:.4}", p.level),
+                    pct(p.mom_pct),
+                    pct(p.yoy_pct),
+                    format!("{:+.2}%", p.cumulative_pct),
+                    fmt_money(point_value(p, direction)),
+                ]
+            })
+            .collect(),
+    }
+}
+
+const SDMX_FORMULA: [&str; 2] = [
+    "Formula (SDMX / CPI index level):",
+    "  real_value = nominal * (CPI_start / CPI_latest)",
+];
+
+const DATAMAPPER_FORMULA: [&str; 3] = [
+    "Formula (DataMapper / PCPIPCH annual %):",
+    "  deflator = Π_y (1 + PCPIPCH_y / 100)",
+    "  real_value = nominal / deflator",
+];
+
+const SDMX_INFLATE_FORMULA: [&str; 2] = [
+    "Formula (SDMX / CPI index level):",
+    "  equivalent = amount * (CPI_latest / CPI_start)",
+];
+
+const BASKET_FORMULA: [&str; 3] = [
+    "Formula (personal basket, chain-linked every December):",
+    "  I_t = I_link * Σ_i w_i * (CPI_i,t / CPI_i,link)",
+    "  real_value = nominal * (I_start / I_latest)",
+];
+
+const BASKET_INFLATE_FORMULA: [&str; 3] = [
+    "Formula (personal basket, chain-linked every December):",
+    "  I_t = I_link * Σ_i w_i * (CPI_i,t / CPI_i,link)",
+    "  equivalent = amount * (I_latest / I_start)",
+];
+
+const DECOMPOSE_FORMULA: [&str; 6] = [
+    "Formula (contribution of each COICOP division, percentage points):",
+    "  share_i = weight_i / weight_all_items   (weights of the start year)",
+    "  contribution_i = share_i * (CPI_i,latest / CPI_i,start - 1) * 100",
+    "  residual = headline inflation - Σ_i contribution_i",
+    "Weights are used as published, not price-updated from their reference period",
+    "to the start: relative price changes in between end up in the residual.",
+];
+
+const DATAMAPPER_INFLATE_FORMULA: [&str; 3] = [
+    "Formula (DataMapper / PCPIPCH annual %):",
+    "  deflator = Π_y (1 + PCPIPCH_y / 100)",
+    "  equivalent = amount * deflator",
+];
+
+const RAISE_FORMULA: &str = "  raise_needed = amount * inflation_factor - current";
+
+const REAL_RAISE_FORMULA: [&str; 5] = [
+    "Formula (real raise; level = CPI index or cumulative PCPIPCH deflator):",
+    "  real_growth = (new / old) / (level_new / level_old) - 1",
+    "  annualized = (1 + real_growth)^(1 / years) - 1",
+    "  surplus_now = (new - old * level_new / level_old) * level_latest / level_new",
+    "  (negative surplus = shortfall)",
+];
+
+const HISTORY_FORMULA: [&str; 2] = [
+    "Formula (history; level = CPI index or cumulative PCPIPCH deflator):",
+    "  real_i = amount_i * (level_base / level_i)",
+];
+
+const DATAMAPPER_NOTE: &str =
+    "Note: DataMapper mode uses annual inflation rates (not monthly CPI index). SDMX mode is more precise.";
+
+/// DataMapper year-boundary convention as shown in the header and documents.
+fn convention_label(ctx: &RunContext<'_>) -> String {
+    if ctx.prorate {
+        "prorated".to_string()
+    } else {
+        ctx.convention.to_string()
+    }
+}
+
+/// Formula lines for a direction, plus the raise line when `--current` is set.
+fn formula_lines<'f>(deflate: &[&'f str], inflate: &[&'f str], direction: Direction, raise: bool) -> Vec<&'f str> {
+    let mut lines = match direction {
+        Direction::Deflate => deflate.to_vec(),
+        Direction::Inflate => inflate.to_vec(),
+    };
+    if raise {
+        lines.push(RAISE_FORMULA);
+    }
+    lines
 }
 
-fn print_formula_datamapper() {
+fn print_formula(lines: &[&str]) {
     println!();
-    println!("Formula (DataMapper / PCPIPCH annual %):");
-    println!("  deflator = Π_y (1 + PCPIPCH_y / 100)");
-    println!("  real_value = nominal / deflator");
+    for l in lines {
+        println!("{}", l);
+    }
+}
+
+// ----------------------- Chart export -----------------------
+/// Real value (deflate) or equivalent (inflate) of `nominal` over every
+/// point of `series`.
+fn adjusted_values(series: &PriceIndexSeries, nominal: f64, direction: Direction) -> Vec<(String, f64)> {
+    series
+        .points
+        .iter()
+        .map(|p| {
+            let v = match direction {
+                Direction::Deflate => compute_real_value(nominal, series.base_level, p.level).real,
+                Direction::Inflate => compute_inflated_value(nominal, series.base_level, p.level).equivalent,
+            };
+            (p.period.clone(), v)
+        })
+        .collect()
+}
+
+/// Nominal vs real value (or equivalent) chart, with any `--compare`
+/// countries fetched over the same range from the same provider and the
+/// `--current` amount as a second reference line.
+async fn build_chart(
+    provider: &dyn CpiProvider,
+    ctx: &RunContext<'_>,
+    range: &PeriodRange,
+    country_code: &str,
+    series: &PriceIndexSeries,
+    nominal: f64,
+    current: Option<f64>,
+) -> Result<Chart> {
+    let value_label = ctx.direction.value_label();
+    let flat = |label: String, amount: f64| ChartLine {
+        label,
+        points: series.points.iter().map(|p| (p.period.clone(), amount)).collect(),
+        reference: true,
+    };
+    let mut lines = vec![flat(format!("Nominal {}", fmt_money(nominal)), nominal)];
+    if let Some(c) = current {
+        lines.push(flat(format!("Current {}", fmt_money(c)), c));
+    }
+    lines.push(ChartLine {
+        label: format!("{} {}", value_label, country_code),
+        points: adjusted_values(series, nominal, ctx.direction),
+        reference: false,
+    });
+    for code in &ctx.compare {
+        let code = code.trim().to_uppercase();
+        let other = provider
+            .fetch_series(&code, range)
+            .await
+            .with_context(|| format!("Cannot fetch {} for the chart", code))?;
+        lines.push(ChartLine {
+            label: format!("{} {}", value_label, code),
+            points: adjusted_values(&other, nominal, ctx.direction),
+            reference: false,
+        });
+    }
+
+    let y_label = match ctx.direction {
+        Direction::Deflate => format!("Value in prices of each period (from {})", series.start_period),
+        Direction::Inflate => format!("Amount matching {} of {}", fmt_money(nominal), series.start_period),
+    };
+    Ok(Chart {
+        title: format!(
+            "Nominal vs {} ({}, {})",
+            value_label.to_lowercase(),
+            provider.source_label(),
+            provider.indicator()
+        ),
+        y_label,
+        lines,
+    })
+}
+
+/// Write `--chart` and `--report` files. Status goes to stderr so it never
+/// mixes with `--format` output.
+fn write_outputs(ctx: &RunContext<'_>, chart: Option<Chart>, report: impl FnOnce(Option<Chart>) -> Report) -> Result<()> {
+    if let (Some(path), Some(chart)) = (&ctx.chart, &chart) {
+        chart.write(path)?;
+        eprintln!("Chart written to {}", path.display());
+    }
+    if let Some(path) = &ctx.report {
+        report(chart).write(path)?;
+        eprintln!("Report written to {}", path.display());
+    }
+    Ok(())
 }
 
 // ----------------------- Jokes -----------------------
@@ -395,591 +1631,953 @@ fn random_joke(loss_pct: f64) -> String {
 }
 
 // ----------------------- SDMX runner -----------------------
+/// Monthly runner, for SDMX and hybrid mode (`sdmx` is the SDMX part of `provider`).
 async fn run_sdmx(
-    client: &Client,
-    cache_dir: &Path,
-    use_cache: bool,
-    verbose: bool,
-    theme: &ColorfulTheme,
-    country_arg: Option<String>,
-    start_input: String,
-    amount: f64,
-    no_jokes: bool,
-    end_input: Option<String>,
+    provider: &dyn CpiProvider,
+    sdmx: &SdmxProvider,
+    mode: Mode,
+    ctx: &RunContext<'_>,
+    query: Query,
 ) -> Result<()> {
-    let start_ym = parse_ym(&start_input).context("Start must be YYYY-MM for SDMX mode")?;
+    let range = PeriodRange::with_frequency(&query.start_input, query.end_input.as_deref(), ctx.frequency)
+        .context("Invalid date range for SDMX mode")?;
 
     // ---- Country selection ----
-    // If user passed --country, don't depend on any metadata/codelist endpoint.
-    // Otherwise load ISO3 country list from SDMX Central and show fuzzy picker.
-    let (country_code, country_name) = match country_arg {
-        Some(code) => {
-            let code_up = code.trim().to_uppercase();
-            (code_up.clone(), code_up) // name fallback = code
-        }
-        None => {
-            let countries = sdmx_load_or_fetch_countries_iso3(client, cache_dir, use_cache).await?;
-            prompt_fuzzy_pick(theme, "Select country (SDMX ISO3)", &countries)?
-        }
-    };
+    let (country_code, country_name) = select_country(provider, mode, ctx, query.country).await?;
 
-    // ---- Date range ----
-    let today = chrono::Utc::now().date_naive();
-    let current_ym = format!("{:04}-{:02}", today.year(), today.month());
+    if ctx.verbose {
+        eprintln!("Mode: {:?}", mode);
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Dataset: {}", sdmx.dataflow());
+        eprintln!("Series key: {}", sdmx.series_key(&country_code));
+        eprintln!("Range: {} → {}", range.sdmx_start()?, range.sdmx_end()?);
+    }
 
-    let mut end_ym = match end_input {
-        Some(ref s) => parse_ym(s).context("End must be YYYY-MM for SDMX mode")?,
-        None => current_ym.clone(),
+    // Fetch CPI values from /data (SDMX-ML XML)
+    let (series, base) = fetch_series_and_base(provider, mode, &country_code, &range, query.base.as_deref()).await?;
+    let latest = series.latest().ok_or_else(|| anyhow!("No CPI data found"))?;
+    // Prices the result is expressed in: --base, or the latest observation.
+    let target = base.clone().unwrap_or_else(|| latest.clone());
+    let inflate = ctx.direction == Direction::Inflate;
+    let AdjustedAmount { result: r, inflated, raise } =
+        adjust_amount(query.amount, series.base_level, target.level, inflate, query.current);
+
+    let points = if ctx.series {
+        // The 12 months before the start, so the first rows get a YoY change too.
+        let earlier = match range.preceding(12) {
+            Some(prev) => match provider.fetch_series(&country_code, &prev).await {
+                // Chained without any SDMX level, a hybrid series starts from its
+                // own arbitrary anchor: not comparable with the main series.
+                Ok(s) if !s.segments.is_empty() && !s.segments.iter().any(|g| g.source == HYBRID_SDMX_SOURCE) => {
+                    Vec::new()
+                }
+                Ok(s) => s.points,
+                Err(e) => {
+                    if ctx.verbose {
+                        eprintln!("No CPI data before {} ({:#}); YoY starts later", range.start, e);
+                    }
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+        Some(real_value_series(query.amount, &series, &earlier))
+    } else {
+        None
     };
 
-    if end_ym > current_ym {
-        end_ym = current_ym.clone();
+    let mut header = header_fields(
+        mode,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &series.start_period,
+        &latest.period,
+    );
+    if let Some(division) = query.coicop.first() {
+        header.push(("COICOP".to_string(), division_label(division)));
+    }
+    if sdmx.is_custom() {
+        header.push((
+            "Series".to_string(),
+            format!("{} {} ({})", sdmx.dataflow(), sdmx.series_key(&country_code), sdmx.frequency()),
+        ));
+    } else if sdmx.index_type() != SDMX_CPI_INDEX_TYPE || sdmx.frequency() != Frequency::Monthly {
+        header.push(("Series".to_string(), format!("{} ({})", sdmx.series_key(&country_code), sdmx.frequency())));
     }
-    if end_ym < start_ym {
-        return Err(anyhow!("--end must be >= start"));
+    if let Some(b) = &base {
+        header.push(("Base".to_string(), base_label(ctx.direction, &b.period, &series.start_period)));
     }
+    let results = result_fields(
+        &r,
+        inflated.as_ref(),
+        raise.as_ref(),
+        &series.start_period,
+        &target.period,
+        base.is_some(),
+    );
 
-    // Convert YYYY-MM -> SDMX monthly periods like 2024-M01
-    let start_period = ym_to_sdmx_period(&start_ym)?;
-    let end_period = ym_to_sdmx_period(&end_ym)?;
+    let mut formula = formula_lines(&SDMX_FORMULA, &SDMX_INFLATE_FORMULA, ctx.direction, raise.is_some());
+    if base.is_some() {
+        formula.push("  (CPI_latest = CPI of the --base period)");
+    }
+    if !series.segments.is_empty() {
+        formula.push("  (months without SDMX data: CPI chained by (1 + PCPIPCH/100)^(1/12) per month)");
+    }
 
-    // CPI key (per your working example):
-    // /data/CPI/POL.CPI._T.IX.M?startPeriod=2024-M01
-    let series_key = format!(
-        "{}.{}.{}.{}.{}",
-        country_code, SDMX_CPI_INDEX_TYPE, SDMX_CPI_COICOP, SDMX_CPI_TRANSFORMATION, SDMX_CPI_FREQ
-    );
+    let chart = if ctx.chart.is_some() || ctx.report.is_some() {
+        Some(build_chart(provider, ctx, &range, &country_code, &series, query.amount, query.current).await?)
+    } else {
+        None
+    };
+    let caption = levels_caption(mode, ctx);
+    write_outputs(ctx, chart, |chart| {
+        let table = match &points {
+            Some(points) => series_report_table(caption, "CPI", points, ctx.direction),
+            None => ReportTable {
+                caption: caption.to_string(),
+                columns: vec!["Period".to_string(), "CPI".to_string()],
+                rows: vec![
+                    vec![series.start_period.clone(), format!("{:.2}", series.base_level)],
+                    vec![target.period.clone(), format!("{:.2}", target.level)],
+                    vec!["Inflation factor".to_string(), format!("{:.4}", r.deflator)],
+                ],
+            },
+        };
+        Report {
+            title: format!("Real income: {}", country_label(&country_name, &country_code)),
+            header: header.clone(),
+            results: results.clone(),
+            table,
+            formula: formula.iter().map(|l| l.to_string()).collect(),
+            notes: source_lines(&series),
+            chart,
+            provenance: provider.retrievals(),
+            generated_at: chrono::Utc::now(),
+        }
+    })?;
 
-    if verbose {
-        eprintln!("Mode: SDMX");
-        eprintln!("Country: {} ({})", country_name, country_code);
-        eprintln!("Dataset: {}", SDMX_CPI_DATASET);
-        eprintln!("Series key: {}", series_key);
-        eprintln!("Range: {} → {}", start_period, end_period);
+    if ctx.format == Format::Csv {
+        print!("{}", series_to_csv(points.as_deref().unwrap_or_default()));
+        return Ok(());
+    }
+    if let Some(format) = ctx.format.document() {
+        let doc = ResultDoc {
+            format: RESULT_FORMAT,
+            version: RESULT_VERSION,
+            mode: mode.id().to_string(),
+            direction: ctx.direction.id().to_string(),
+            convention: None,
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            coicop: query.coicop.first().map(|d| d.code.clone()),
+            index_type: (!sdmx.is_custom()).then(|| sdmx.index_type().to_string()),
+            requested: RequestedPeriods { start: range.start.clone(), end: range.end.clone() },
+            used: UsedPeriods { start: series.start_period.clone(), latest: latest.period.clone() },
+            cpi: Some(CpiLevels {
+                start: IndexPoint { period: series.start_period.clone(), level: series.base_level },
+                latest: latest.clone(),
+            }),
+            pcpipch: None,
+            base,
+            result: r,
+            inflated,
+            raise,
+            series: points,
+            segments: segments(&series),
+        };
+        return print_document(&doc, format);
     }
 
-    // Fetch CPI values from /data (SDMX-ML XML)
-    let (start_period_used, cpi_start, latest_period, cpi_latest) =
-        sdmx_fetch_cpi_start_and_latest(
-            client,
-            cache_dir,
-            use_cache,
-            &series_key,
-            &start_period,
-            &end_period,
-        )
-        .await?;
-
-    let start_label = sdmx_period_to_ym(&start_period_used);
-    let latest_label = sdmx_period_to_ym(&latest_period);
-    let ratio = cpi_start / cpi_latest;
-    let real_now = amount * ratio;
-    let loss = amount - real_now;
-    let loss_pct = (1.0 - ratio) * 100.0;
-
-    print_header(
-        Mode::Sdmx,
-        &country_name,
-        "IMF SDMX",
-        "CPI index level",
-        &start_label,
-        &latest_label,
-    );
-    print_results(amount, real_now, loss, loss_pct);
+    print_header(&header);
+    print_results(&results);
+    print_sources(&series);
 
     println!();
-    println!("CPI index levels used (SDMX):");
-    println!("  {}: {:.2}", start_label, cpi_start);
-    println!("  {}: {:.2}", latest_label, cpi_latest);
-    println!("  Inflation factor: {:.4}", cpi_latest / cpi_start);
+    println!("{}:", caption);
+    println!("  {}: {:.2}", series.start_period, series.base_level);
+    println!("  {}: {:.2}", target.period, target.level);
+    println!("  Inflation factor: {:.4}", r.deflator);
 
-    println!();
-    println!("Formula (SDMX / CPI index level):");
-    println!("  real_value = nominal * (CPI_start / CPI_latest)");
+    if let Some(points) = &points {
+        println!();
+        print_series_table(points, "CPI", ctx.direction);
+    }
 
-    if !no_jokes {
+    if let Some(style) = ctx.plot {
         println!();
-        println!("{}", random_joke(loss_pct));
+        print_plots(style, &series, query.amount, ctx.direction, "CPI index", ctx.frequency.unit());
+    }
+
+    print_formula(&formula);
+
+    if !ctx.no_jokes {
+        println!();
+        println!("{}", random_joke(r.loss_pct));
     }
 
     Ok(())
 }
 
-// ----------------------- DataMapper runner -----------------------
-async fn run_datamapper(
-    client: &Client,
-    cache_dir: &Path,
-    use_cache: bool,
-    verbose: bool,
-    theme: &ColorfulTheme,
-    country_arg: Option<String>,
-    start_input: String,
-    amount: f64,
-    no_jokes: bool,
-    end_input: Option<String>,
-) -> Result<()> {
-    let start_year =
-        parse_year_loose(&start_input).context("Start must be YYYY (or YYYY-MM) for DataMapper mode")?;
-
-    let countries = datamapper_list_countries(client, cache_dir, use_cache).await?;
-    let (country_code, country_name) = match country_arg {
-        Some(code) => {
-            let code_up = code.trim().to_uppercase();
-            let name = countries
-                .iter()
-                .find(|x| x.code == code_up)
-                .map(|x| x.name.clone())
-                .ok_or_else(|| anyhow!("Country code '{}' not found in DataMapper countries list", code_up))?;
-            (code_up, name)
-        }
-        None => prompt_fuzzy_pick(theme, "Select country (DataMapper ISO3)", &countries)?,
-    };
-
-    let current_year = chrono::Utc::now().date_naive().year();
-    let mut end_year_used = match end_input {
-        Some(ref s) => parse_year_loose(s).context("End must be YYYY (or YYYY-MM) for DataMapper mode")?,
-        None => current_year,
-    };
+// ----------------------- COICOP components runner -----------------------
+/// Several COICOP divisions over the same range, side by side.
+async fn run_components(provider: &SdmxProvider, ctx: &RunContext<'_>, query: Query) -> Result<()> {
+    let range = PeriodRange::with_frequency(&query.start_input, query.end_input.as_deref(), ctx.frequency)
+        .context("Invalid date range for SDMX mode")?;
 
-    if end_year_used > current_year {
-        end_year_used = current_year;
-    }
-    if end_year_used < start_year {
-        return Err(anyhow!("--end must be >= start year"));
-    }
+    let (country_code, country_name) = select_country(provider, Mode::Sdmx, ctx, query.country).await?;
 
-    if verbose {
-        eprintln!("Mode: DataMapper");
+    if ctx.verbose {
+        eprintln!("Mode: SDMX (COICOP divisions)");
         eprintln!("Country: {} ({})", country_name, country_code);
-        eprintln!("Indicator: {}", DATAMAPPER_INDICATOR);
-        eprintln!("Years: {} → {}", start_year, end_year_used);
+        eprintln!("Dataset: {}", SDMX_CPI_DATASET);
+        eprintln!("Range: {} → {}", range.sdmx_start()?, range.sdmx_end()?);
     }
 
-    let (deflator, latest_year, yearly) = datamapper_deflator_and_yearly_pcpipch(
-        client,
-        cache_dir,
-        use_cache,
-        &country_code,
-        start_year,
-        end_year_used,
-    )
-    .await?;
+    let mut components = Vec::with_capacity(query.coicop.len());
+    for division in &query.coicop {
+        // Clones share the client: every request lands in one provenance log.
+        let division_provider = provider.clone().with_coicop(&division.code);
+        if ctx.verbose {
+            eprintln!("Series key: {}", division_provider.series_key(&country_code));
+        }
+        let (series, base) =
+            fetch_series_and_base(&division_provider, Mode::Sdmx, &country_code, &range, query.base.as_deref())
+                .await
+                .with_context(|| format!("COICOP division {}", division.code))?;
+        let inflate = ctx.direction == Direction::Inflate;
+        components.push(ComponentResult::from_series(division, &series, base, query.amount, inflate)?);
+    }
 
-    let real_now = amount / deflator;
-    let loss = amount - real_now;
-    let loss_pct = (1.0 - (1.0 / deflator)) * 100.0;
+    if let Some(format) = ctx.format.document() {
+        let doc = ComponentsDoc {
+            format: COMPONENTS_FORMAT,
+            version: RESULT_VERSION,
+            mode: Mode::Sdmx.id().to_string(),
+            direction: ctx.direction.id().to_string(),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            requested: RequestedPeriods { start: range.start.clone(), end: range.end.clone() },
+            components,
+        };
+        return print_document(&doc, format);
+    }
 
-    print_header(
-        Mode::Datamapper,
+    let latest = components.iter().map(|c| c.used.latest.as_str()).max().unwrap_or_default().to_string();
+    let mut header = header_fields(
+        Mode::Sdmx,
         &country_name,
-        "IMF DataMapper",
-        DATAMAPPER_INDICATOR,
-        &start_year.to_string(),
-        &latest_year.to_string(),
+        provider.source_label(),
+        provider.indicator(),
+        &range.start,
+        &latest,
     );
-    print_results(amount, real_now, loss, loss_pct);
-
+    if let Some(b) = components.first().and_then(|c| c.base.as_ref()) {
+        header.push(("Base".to_string(), base_label(ctx.direction, &b.period, &range.start)));
+    }
+    print_header(&header);
+    println!("Nominal amount: {}", fmt_money(query.amount));
     println!();
-    println!("Annual inflation rates used (PCPIPCH):");
-    for yi in &yearly {
-        println!("  {}: {:+.2}%", yi.year, yi.pct);
+
+    let labels: Vec<String> = components.iter().map(|c| division_label(&c.coicop)).collect();
+    let label_w = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0).max("COICOP".len());
+    println!(
+        "{:<lw$} {:>8} {:>9} {:>8} {:>9} {:>10} {:>14}",
+        "COICOP",
+        "Start",
+        "CPI",
+        if query.base.is_some() { "Base" } else { "Latest" },
+        "CPI",
+        "Inflation",
+        ctx.direction.value_label(),
+        lw = label_w
+    );
+    for (c, label) in components.iter().zip(&labels) {
+        let target = c.base.as_ref().unwrap_or(&c.cpi.latest);
+        let value = match &c.inflated {
+            Some(i) => i.equivalent,
+            None => c.result.real,
+        };
+        println!(
+            "{:<lw$} {:>8} {:>9.2} {:>8} {:>9.2} {:>9.2}% {:>14}",
+            label,
+            c.cpi.start.period,
+            c.cpi.start.level,
+            target.period,
+            target.level,
+            (c.result.deflator - 1.0) * 100.0,
+            fmt_money(value),
+            lw = label_w
+        );
     }
 
-    print_formula_datamapper();
+    print_formula(&formula_lines(&SDMX_FORMULA, &SDMX_INFLATE_FORMULA, ctx.direction, false));
 
-    println!();
-    println!("Note: DataMapper mode uses annual inflation rates (not monthly CPI index). SDMX mode is more precise.");
-
-    if !no_jokes {
-        println!();
-        println!("{}", random_joke(loss_pct));
+    if !ctx.no_jokes {
+        // The division that hurt the most.
+        if let Some(worst) = components.iter().map(|c| c.result.loss_pct).max_by(f64::total_cmp) {
+            println!();
+            println!("{}", random_joke(worst));
+        }
     }
 
     Ok(())
 }
 
-// ----------------------- SDMX: fetch ISO3 list (with retries + cache) -----------------------
-async fn sdmx_load_or_fetch_countries_iso3(
-    client: &Client,
-    cache_dir: &Path,
-    use_cache: bool,
-) -> Result<Vec<Item>> {
-    let cache_file = cache_dir.join("sdmx_countries_iso3.xml");
-
-    let bytes = if use_cache { fs::read(&cache_file).ok() } else { None };
-
-    let xml_bytes = match bytes {
-        Some(b) => b,
-        None => {
-            // Fetch ONLY the ISO3 country codelist
-            // (This matches the XML you pasted and includes POL, RUS, CHE, USA, etc.)
-            let url = format!(
-                "{}/codelist/IMF/{}/latest",
-                IMF_SDMX_STRUCTURE_BASE,
-                SDMX_CL_AREA_CPI
-            );
+// ----------------------- Personal basket runner -----------------------
+/// Chain-linked index of the basket's divisions, next to headline CPI.
+async fn run_basket(provider: &SdmxProvider, ctx: &RunContext<'_>, query: Query, basket: Vec<BasketItem>) -> Result<()> {
+    let range = PeriodRange::with_frequency(&query.start_input, query.end_input.as_deref(), ctx.frequency)
+        .context("Invalid date range for SDMX mode")?;
 
-            let resp = client
-                .get(url)
-                .send()
-                .await
-                .context("HTTP error fetching SDMX Central country codelist")?
-                .error_for_status()
-                .context("SDMX Central country codelist returned non-OK status")?;
+    let (country_code, country_name) = select_country(provider, Mode::Sdmx, ctx, query.country).await?;
 
-            let b = resp.bytes().await?.to_vec();
-            if use_cache {
-                let _ = fs::write(&cache_file, &b);
-            }
-            b
+    if ctx.verbose {
+        eprintln!("Mode: SDMX (personal basket)");
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Dataset: {}", SDMX_CPI_DATASET);
+        eprintln!("Range: {} → {}", range.sdmx_start()?, range.sdmx_end()?);
+    }
+
+    let mut components = Vec::with_capacity(basket.len());
+    for item in &basket {
+        // Clones share the client: every request lands in one provenance log.
+        let division_provider = provider.clone().with_coicop(&item.coicop);
+        if ctx.verbose {
+            eprintln!("Series key: {} (weight {:.4})", division_provider.series_key(&country_code), item.weight);
         }
-    };
+        let series = division_provider
+            .fetch_series(&country_code, &range)
+            .await
+            .with_context(|| format!("COICOP division {}", item.coicop))?;
+        components.push(series);
+    }
+    // Headline over exactly the same periods.
+    let headline = provider.fetch_series(&country_code, &range).await.context("Headline CPI")?;
+    let BasketComparison { index: personal, personal: personal_result, headline: headline_result } =
+        compare_basket(&basket, &components, &headline, query.amount, ctx.direction == Direction::Inflate)?;
+    let latest = personal_result.levels.latest.clone();
+    let points = ctx.series.then(|| real_value_series(query.amount, &personal, &[]));
+
+    if ctx.format == Format::Csv {
+        print!("{}", series_to_csv(points.as_deref().unwrap_or_default()));
+        return Ok(());
+    }
+    if let Some(format) = ctx.format.document() {
+        let doc = BasketDoc {
+            format: BASKET_FORMAT,
+            version: RESULT_VERSION,
+            mode: Mode::Sdmx.id().to_string(),
+            direction: ctx.direction.id().to_string(),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            requested: RequestedPeriods { start: range.start.clone(), end: range.end.clone() },
+            used: UsedPeriods { start: personal.start_period.clone(), latest: latest.period.clone() },
+            basket,
+            personal: personal_result,
+            headline: headline_result,
+            series: points,
+        };
+        return print_document(&doc, format);
+    }
 
-    // Parse:
-    // <str:Code id="POL"><com:Name xml:lang="en">Poland</com:Name></str:Code>
-    let mut reader = Reader::from_reader(xml_bytes.as_slice());
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut out: Vec<Item> = Vec::new();
-
-    let mut in_code = false;
-    let mut current_id: Option<String> = None;
-    let mut current_name: Option<String> = None;
-    let mut capture_name_text = false;
-
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                // quick-xml 0.31: avoid borrowing a temporary from e.name().as_ref()
-                let name = e.name().as_ref().to_vec();
-
-                if name.ends_with(b"Code") {
-                    in_code = true;
-                    current_id = None;
-                    current_name = None;
-
-                    for a in e.attributes().with_checks(false) {
-                        let a = a?;
-                        if a.key.as_ref().ends_with(b"id") {
-                            current_id = Some(a.unescape_value()?.to_string());
-                        }
-                    }
-                } else if in_code && name.ends_with(b"Name") {
-                    // Prefer xml:lang="en"
-                    let mut is_en = false;
-                    for a in e.attributes().with_checks(false) {
-                        let a = a?;
-                        if a.key.as_ref().ends_with(b"lang") {
-                            let v = a.unescape_value()?;
-                            if v.eq_ignore_ascii_case("en") {
-                                is_en = true;
-                            }
-                        }
-                    }
+    print_header(&header_fields(
+        Mode::Sdmx,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &personal.start_period,
+        &latest.period,
+    ));
+    println!("Basket (weights normalised to 100%):");
+    for item in &basket {
+        let label = item.label.as_deref().map(|l| format!(" {}", l)).unwrap_or_default();
+        println!("  {}{}: {:.2}%", item.coicop, label, item.weight * 100.0);
+    }
+    println!();
 
-                    // Capture if English OR if we don't have a name yet
-                    capture_name_text = is_en || current_name.is_none();
-                }
-            }
+    let inflation = |r: &IndexResult| (r.result.deflator - 1.0) * 100.0;
+    let value = |r: &IndexResult| r.inflated.as_ref().map_or(r.result.real, |i| i.equivalent);
+    println!("Nominal amount: {}", fmt_money(query.amount));
+    println!("{:<22} {:>14} {:>14}", "", "Personal", "Headline");
+    println!(
+        "{:<22} {:>13.2}% {:>13.2}%",
+        "Inflation",
+        inflation(&personal_result),
+        inflation(&headline_result)
+    );
+    println!(
+        "{:<22} {:>14} {:>14}",
+        ctx.direction.value_label(),
+        fmt_money(value(&personal_result)),
+        fmt_money(value(&headline_result))
+    );
+    println!(
+        "{:<22} {:>13.2}% {:>13.2}%",
+        "Purchasing-power loss",
+        personal_result.result.loss_pct,
+        headline_result.result.loss_pct
+    );
+    let gap = inflation(&personal_result) - inflation(&headline_result);
+    println!(
+        "Your basket's inflation is {:.2} pp {} headline.",
+        gap.abs(),
+        if gap >= 0.0 { "above" } else { "below" }
+    );
 
-            Ok(Event::Text(t)) => {
-                if in_code && capture_name_text {
-                    current_name = Some(t.unescape()?.to_string());
-                }
-            }
+    if let Some(points) = &points {
+        println!();
+        print_series_table(points, "Index", ctx.direction);
+    }
 
-            Ok(Event::End(ref e)) => {
-                let name = e.name().as_ref().to_vec();
+    print_formula(&formula_lines(&BASKET_FORMULA, &BASKET_INFLATE_FORMULA, ctx.direction, false));
 
-                if name.ends_with(b"Name") {
-                    capture_name_text = false;
-                }
+    if !ctx.no_jokes {
+        println!();
+        println!("{}", random_joke(personal_result.result.loss_pct));
+    }
 
-                if name.ends_with(b"Code") && in_code {
-                    if let Some(id) = current_id.take() {
-                        let name = current_name.take().unwrap_or_else(|| id.clone());
-                        out.push(Item { code: id, name });
-                    }
-                    in_code = false;
-                }
-            }
+    Ok(())
+}
 
-            Ok(Event::Eof) => break,
-            Err(e) => return Err(anyhow!(e).context("Invalid SDMX Central codelist XML")),
-            _ => {}
-        }
+// ----------------------- Decomposition runner -----------------------
+/// Percentage-point contribution of each COICOP division to headline inflation.
+async fn run_decompose(provider: &SdmxProvider, ctx: &RunContext<'_>, query: DecomposeQuery) -> Result<()> {
+    let range = PeriodRange::with_frequency(&query.start_input, query.end_input.as_deref(), ctx.frequency)
+        .context("Invalid date range for SDMX mode")?;
+
+    let (country_code, country_name) = select_country(provider, Mode::Sdmx, ctx, query.country).await?;
 
-        buf.clear();
+    if ctx.verbose {
+        eprintln!("Mode: SDMX (inflation decomposition)");
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Dataset: {}", SDMX_CPI_DATASET);
+        eprintln!("Range: {} → {}", range.sdmx_start()?, range.sdmx_end()?);
     }
 
-    if out.is_empty() {
-        return Err(anyhow!("Parsed 0 country codes from SDMX Central"));
+    let headline = provider.fetch_series(&country_code, &range).await.context("Headline CPI")?;
+    let all_items_weights = provider.fetch_weights(&country_code).await?;
+
+    let mut divisions = Vec::new();
+    for coicop in provider
+        .load_or_fetch_coicop()
+        .await?
+        .into_iter()
+        .filter(|d| d.code.len() == 4 && d.code.starts_with("CP"))
+    {
+        // Clones share the client: every request lands in one provenance log.
+        let division_provider = provider.clone().with_coicop(&coicop.code);
+        // Unpublished divisions (no observations, 404) are skipped; failed requests are errors.
+        let series = division_provider
+            .fetch_index(&country_code, &range)
+            .await
+            .with_context(|| format!("{} index", coicop.code))?;
+        let weights = division_provider
+            .fetch_weights(&country_code)
+            .await
+            .with_context(|| format!("{} weights", coicop.code))?;
+        if ctx.verbose && (series.is_none() || weights.is_empty()) {
+            eprintln!("{}: no index or no weights published", coicop.code);
+        }
+        divisions.push(DivisionData { coicop, series, weights });
+    }
+    let HeadlineDecomposition { start, latest, result, skipped } =
+        decompose_headline(&headline, &all_items_weights, divisions)?;
+
+    if let Some(format) = ctx.format.document() {
+        let doc = DecompositionDoc {
+            format: DECOMPOSITION_FORMAT,
+            version: RESULT_VERSION,
+            mode: Mode::Sdmx.id().to_string(),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            requested: RequestedPeriods { start: range.start.clone(), end: range.end.clone() },
+            used: UsedPeriods { start: start.period.clone(), latest: latest.period.clone() },
+            headline: CpiLevels { start, latest },
+            result,
+            skipped,
+        };
+        return print_document(&doc, format);
     }
 
-    out.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
-    Ok(out)
+    print_header(&header_fields(
+        Mode::Sdmx,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &start.period,
+        &latest.period,
+    ));
+    println!("Headline inflation: {:.2}%", result.headline_pct);
+    println!();
+
+    let labels: Vec<String> = result.contributions.iter().map(|c| division_label(&c.coicop)).collect();
+    let label_w = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0).max("Residual".len());
+    println!(
+        "{:<lw$} {:>8} {:>10} {:>13}",
+        "COICOP",
+        "Weight",
+        "Inflation",
+        "Contribution",
+        lw = label_w
+    );
+    for (c, label) in result.contributions.iter().zip(&labels) {
+        println!(
+            "{:<lw$} {:>7.2}% {:>9.2}% {:>10.2} pp",
+            label,
+            c.share * 100.0,
+            c.inflation_pct,
+            c.contribution_pp,
+            lw = label_w
+        );
+    }
+    println!("{:<lw$} {:>8} {:>10} {:>10.2} pp", "Explained", "", "", result.explained_pp, lw = label_w);
+    println!("{:<lw$} {:>8} {:>10} {:>10.2} pp", "Residual", "", "", result.residual_pp, lw = label_w);
+    if !skipped.is_empty() {
+        let codes: Vec<&str> = skipped.iter().map(|d| d.code.as_str()).collect();
+        println!();
+        println!("Skipped (no index or weight): {}", codes.join(", "));
+    }
+
+    print_formula(&DECOMPOSE_FORMULA);
+
+    Ok(())
 }
 
-// ----------------------- SDMX: fetch CPI start + latest (NEW /data + SDMX-ML XML) -----------------------
-async fn sdmx_fetch_cpi_start_and_latest(
-    client: &Client,
-    cache_dir: &Path,
-    use_cache: bool,
-    series_key: &str,
-    start_period: &str,
-    end_period: &str,
-) -> Result<(String, f64, String, f64)> {
-    let cache_key = format!(
-        "sdmx_cpi_xml_{}_{}_{}.xml",
-        series_key.replace('.', "_"),
-        start_period.replace('-', ""),
-        end_period.replace('-', "")
+// ----------------------- DataMapper runner -----------------------
+async fn run_datamapper(provider: &DataMapperProvider, ctx: &RunContext<'_>, query: Query) -> Result<()> {
+    let range = if ctx.prorate {
+        PeriodRange::yearly_with_months(&query.start_input, query.end_input.as_deref())
+    } else {
+        PeriodRange::yearly(&query.start_input, query.end_input.as_deref())
+    }
+    .context("Invalid date range for DataMapper mode")?;
+
+    let (country_code, country_name) = select_country(provider, Mode::Datamapper, ctx, query.country).await?;
+
+    if ctx.verbose {
+        eprintln!("Mode: DataMapper");
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Indicator: {}", DATAMAPPER_INDICATOR);
+        eprintln!("Years: {} → {}", range.start_year(), range.end_year());
+    }
+
+    let (series, base) =
+        fetch_series_and_base(provider, Mode::Datamapper, &country_code, &range, query.base.as_deref()).await?;
+    let latest = series.latest().ok_or_else(|| anyhow!("No numeric observations found"))?;
+    let target = base.clone().unwrap_or_else(|| latest.clone());
+    let inflate = ctx.direction == Direction::Inflate;
+    let AdjustedAmount { result: r, inflated, raise } =
+        adjust_amount(query.amount, series.base_level, target.level, inflate, query.current);
+    let points = ctx.series.then(|| real_value_series(query.amount, &series, &[]));
+
+    let mut header = header_fields(
+        Mode::Datamapper,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &series.start_period,
+        &latest.period,
+    );
+    if let Some(b) = &base {
+        // Deflator levels: the base year is priced at its start (like the
+        // start year and --history records), the latest one at its end.
+        header.push((
+            "Base".to_string(),
+            format!(
+                "{}; deflator at the start of {}, Latest at the end of {}",
+                base_label(ctx.direction, &b.period, &series.start_period),
+                b.period,
+                latest.period
+            ),
+        ));
+    }
+    header.push(("Year convention".to_string(), convention_label(ctx)));
+    let results = result_fields(
+        &r,
+        inflated.as_ref(),
+        raise.as_ref(),
+        &series.start_period,
+        &target.period,
+        base.is_some(),
     );
-    let cache_file = cache_dir.join(cache_key);
-
-    let bytes = if use_cache { fs::read(&cache_file).ok() } else { None };
-
-    let xml_bytes = match bytes {
-        Some(b) => b,
-        None => {
-            // NEW ENDPOINT:
-            //   /data/CPI/{series_key}?startPeriod=YYYY-MMM&endPeriod=YYYY-MMM
-            let url = format!(
-                "{}/data/{}/{}?startPeriod={}&endPeriod={}",
-                IMF_SDMX_BASE, SDMX_CPI_DATASET, series_key, start_period, end_period
-            );
 
-            let resp = client
-                .get(url)
-                .send()
-                .await
-                .context("HTTP error fetching SDMX data")?
-                .error_for_status()
-                .context("SDMX data returned non-OK status")?;
+    let mut formula = formula_lines(&DATAMAPPER_FORMULA, &DATAMAPPER_INFLATE_FORMULA, ctx.direction, raise.is_some());
+    if series.yearly.iter().any(|yi| yi.months.is_some()) {
+        formula.insert(2, "  (boundary years: (1 + PCPIPCH_y / 100)^(months_y / 12))");
+    }
+    if !ctx.prorate && ctx.convention == YearConvention::AveragePriceLevel {
+        formula.insert(2, "  (average-price-level: deflator = avg_last / avg_first, with");
+        formula.insert(3, "   avg_y = level at the start of y * (g_y - 1) / ln g_y, g_y = 1 + PCPIPCH_y / 100)");
+    }
 
-            let b = resp.bytes().await?.to_vec();
-            if use_cache {
-                let _ = fs::write(&cache_file, &b);
-            }
-            b
-        }
+    let chart = if ctx.chart.is_some() || ctx.report.is_some() {
+        Some(build_chart(provider, ctx, &range, &country_code, &series, query.amount, query.current).await?)
+    } else {
+        None
     };
+    write_outputs(ctx, chart, |chart| {
+        let table = match &points {
+            Some(points) => series_report_table("Annual inflation rates used (PCPIPCH)", "Deflator", points, ctx.direction),
+            None => ReportTable {
+                caption: "Annual inflation rates used (PCPIPCH)".to_string(),
+                columns: vec!["Year".to_string(), "PCPIPCH".to_string()],
+                rows: series
+                    .yearly
+                    .iter()
+                    .map(|yi| {
+                        let pct = match yi.months {
+                            Some(m) => format!("{:+.2}% ({}/12 of the year)", yi.pct, m),
+                            None => format!("{:+.2}%", yi.pct),
+                        };
+                        vec![yi.year.to_string(), pct]
+                    })
+                    .collect(),
+            },
+        };
+        Report {
+            title: format!("Real income: {}", country_label(&country_name, &country_code)),
+            header: header.clone(),
+            results: results.clone(),
+            table,
+            formula: formula.iter().map(|l| l.to_string()).collect(),
+            notes: vec![DATAMAPPER_NOTE.to_string()],
+            chart,
+            provenance: provider.retrievals(),
+            generated_at: chrono::Utc::now(),
+        }
+    })?;
 
-    // Parse <Obs TIME_PERIOD="2020-M01" OBS_VALUE="..." .../>
-    let mut reader = Reader::from_reader(xml_bytes.as_slice());
-    reader.trim_text(true);
-
-    let mut buf = Vec::new();
-    let mut obs: Vec<(String, f64)> = Vec::new();
-
-    loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Empty(e)) | Ok(Event::Start(e)) => {
-                // In this feed, Obs is typically <Obs .../>
-                if e.name().as_ref().ends_with(b"Obs") {
-                    let mut tp: Option<String> = None;
-                    let mut val: Option<f64> = None;
-
-                    for a in e.attributes().with_checks(false) {
-                        let a = a?;
-                        let k = a.key.as_ref();
-
-                        if k.ends_with(b"TIME_PERIOD") {
-                            tp = Some(a.unescape_value()?.to_string());
-                        } else if k.ends_with(b"OBS_VALUE") {
-                            let s = a.unescape_value()?;
-                            val = Some(s.parse::<f64>().context("OBS_VALUE not numeric")?);
-                        }
-                    }
+    if ctx.format == Format::Csv {
+        print!("{}", series_to_csv(points.as_deref().unwrap_or_default()));
+        return Ok(());
+    }
+    if let Some(format) = ctx.format.document() {
+        let doc = ResultDoc {
+            format: RESULT_FORMAT,
+            version: RESULT_VERSION,
+            mode: Mode::Datamapper.id().to_string(),
+            direction: ctx.direction.id().to_string(),
+            convention: Some(convention_label(ctx)),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            coicop: None,
+            index_type: None,
+            requested: RequestedPeriods {
+                start: range.start_year().to_string(),
+                end: range.end_year().to_string(),
+            },
+            used: UsedPeriods { start: series.start_period.clone(), latest: latest.period.clone() },
+            cpi: None,
+            pcpipch: Some(series.yearly.clone()),
+            base,
+            result: r,
+            inflated,
+            raise,
+            series: points,
+            segments: None,
+        };
+        return print_document(&doc, format);
+    }
 
-                    if let (Some(t), Some(v)) = (tp, val) {
-                        if v > 0.0 {
-                            obs.push((t, v));
-                        }
-                    }
-                }
-            }
-            Ok(Event::Eof) => break,
-            Err(e) => return Err(anyhow!(e).context("Invalid SDMX XML")),
-            _ => {}
+    print_header(&header);
+    print_results(&results);
+
+    println!();
+    println!("Annual inflation rates used (PCPIPCH):");
+    for yi in &series.yearly {
+        match yi.months {
+            Some(m) => println!(
+                "  {}: {:+.2}% ({}/12 of the year: {:+.2}%)",
+                yi.year,
+                yi.pct,
+                m,
+                ((1.0 + yi.pct / 100.0).powf(m as f64 / 12.0) - 1.0) * 100.0
+            ),
+            None => println!("  {}: {:+.2}%", yi.year, yi.pct),
         }
-        buf.clear();
+    }
+    if let Some(b) = &base {
+        println!(
+            "  Inflation factor from the start of {} to the start of {}: {:.4}",
+            series.start_period, b.period, r.deflator
+        );
+    }
+
+    if let Some(points) = &points {
+        println!();
+        print_series_table(points, "Deflator", ctx.direction);
     }
 
-    if obs.is_empty() {
-        return Err(anyhow!("No observations found in SDMX XML response"));
+    if let Some(style) = ctx.plot {
+        println!();
+        print_plots(style, &series, query.amount, ctx.direction, "Cumulative deflator", "year");
     }
 
-    // TIME_PERIOD sorts lexicographically for "YYYY-MMM" format
-    obs.sort_by(|a, b| a.0.cmp(&b.0));
+    print_formula(&formula);
 
-    let start_obs = obs
-        .iter()
-        .find(|(t, _)| t.as_str() >= start_period)
-        .ok_or_else(|| anyhow!("No CPI data found at/after start date (start too early?)"))?;
-
-    let latest_obs = obs.last().ok_or_else(|| anyhow!("No CPI data found"))?;
-
-    let cpi_start = start_obs.1;
-    let cpi_latest = latest_obs.1;
-
-    if cpi_start <= 0.0 || cpi_latest <= 0.0 {
-        return Err(anyhow!("Invalid CPI values (<= 0)"));
-    }
-
-    Ok((
-        start_obs.0.clone(),
-        cpi_start,
-        latest_obs.0.clone(),
-        cpi_latest,
-    ))
-}
-
-// ----------------------- DataMapper: anti-403 client -----------------------
-fn build_datamapper_client() -> Result<Client> {
-    Client::builder()
-        .http1_only()
-        .cookie_store(true)
-        .user_agent("curl/8.5.0")
-        .default_headers({
-            let mut h = reqwest::header::HeaderMap::new();
-            h.insert(ACCEPT, "application/json,text/plain,*/*".parse().unwrap());
-            h.insert(ACCEPT_LANGUAGE, "en-US,en;q=0.9".parse().unwrap());
-            h.insert(REFERER, "https://www.imf.org/external/datamapper/".parse().unwrap());
-            h
-        })
-        .build()
-        .context("Failed to build DataMapper HTTP client")
-}
+    println!();
+    println!("{}", DATAMAPPER_NOTE);
+
+    if !ctx.no_jokes {
+        println!();
+        println!("{}", random_joke(r.loss_pct));
+    }
 
-// ----------------------- DataMapper: list countries -----------------------
-async fn datamapper_list_countries(client: &Client, cache_dir: &Path, use_cache: bool) -> Result<Vec<Item>> {
-    let cache_file = cache_dir.join("dm_countries.json");
+    Ok(())
+}
 
-    if use_cache {
-        if let Ok(b) = fs::read(&cache_file) {
-            if let Ok(v) = serde_json::from_slice::<Vec<Item>>(&b) {
-                if !v.is_empty() {
-                    return Ok(v);
-                }
-            }
+// ----------------------- Real raise runner -----------------------
+async fn run_raise(provider: &dyn CpiProvider, mode: Mode, ctx: &RunContext<'_>, query: RaiseQuery) -> Result<()> {
+    let (old_period, new_period, range) = match mode {
+        Mode::Sdmx | Mode::Hybrid => {
+            let old = ctx.frequency.convert(&query.old.0, false).context("Old date must be YYYY-MM, YYYY-Qn or YYYY")?;
+            let new = ctx.frequency.convert(&query.new.0, false).context("New date must be YYYY-MM, YYYY-Qn or YYYY")?;
+            let range = PeriodRange::with_frequency(&old, None, ctx.frequency)
+                .context("Invalid date range for SDMX mode")?;
+            (old, new, range)
+        }
+        Mode::Datamapper => {
+            let old = parse_year_loose(&query.old.0).context("Old date must be YYYY (or YYYY-MM)")?;
+            let new = parse_year_loose(&query.new.0).context("New date must be YYYY (or YYYY-MM)")?;
+            let range = PeriodRange::yearly(&old.to_string(), None).context("Invalid date range for DataMapper mode")?;
+            (format!("{:04}", old), format!("{:04}", new), range)
         }
+    };
+    if new_period < old_period {
+        return Err(anyhow!("The new date ({}) is before the old one ({})", new_period, old_period));
     }
+    let base = query.base.as_deref().map(|b| base_range(mode, ctx.frequency, b)).transpose()?;
+    let range = match &base {
+        Some((_, b)) => range.including(b),
+        None => range,
+    };
 
-    let url = format!("{}/countries", IMF_DATAMAPPER_BASE);
-    let resp = client
-        .get(url)
-        .send()
-        .await
-        .context("HTTP error fetching DataMapper countries")?;
+    let (country_code, country_name) = select_country(provider, mode, ctx, query.country).await?;
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        return Err(anyhow!("DataMapper countries returned {}.\nBody:\n{}", status, body));
+    if ctx.verbose {
+        eprintln!("Mode: {:?} (real raise)", mode);
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Old: {} {}", old_period, query.old.1);
+        eprintln!("New: {} {}", new_period, query.new.1);
     }
 
-    let json: Value = resp.json().await.context("Invalid JSON from DataMapper countries")?;
-    let obj = json.get("countries").cloned().unwrap_or(json);
-    let map = obj
-        .as_object()
-        .ok_or_else(|| anyhow!("Unexpected DataMapper countries JSON shape"))?;
+    let series = provider.fetch_series(&country_code, &range).await?;
+    let RaiseComparison { old, new, latest, target, result: r } = compare_raise(
+        &series,
+        (&old_period, query.old.1),
+        (&new_period, query.new.1),
+        base.as_ref().map(|(p, _)| p.as_str()),
+    )?;
+
+    if let Some(format) = ctx.format.document() {
+        let doc = RaiseDoc {
+            format: RAISE_FORMAT,
+            version: RESULT_VERSION,
+            mode: mode.id().to_string(),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            old,
+            new,
+            latest,
+            base: base.map(|_| target),
+            result: r,
+            segments: segments(&series),
+        };
+        return print_document(&doc, format);
+    }
 
-    let mut out = Vec::with_capacity(map.len());
-    for (code, info) in map {
-        let name = info
-            .get("label")
-            .and_then(|x| x.as_str())
-            .unwrap_or(code)
-            .to_string();
-        out.push(Item { code: code.to_string(), name });
+    print_header(&header_fields(
+        mode,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &old_period,
+        &latest.period,
+    ));
+    print_sources(&series);
+    println!("Old amount ({}): {}", old_period, fmt_money(r.old_amount));
+    println!("New amount ({}): {}", new_period, fmt_money(r.new_amount));
+    println!("Nominal growth: {:+.2}%", r.nominal_growth_pct);
+    println!("Cumulative inflation: {:+.2}%", r.inflation_pct);
+    println!("Real growth: {:+.2}%", r.real_growth_pct);
+    match r.annualized_real_growth_pct {
+        Some(a) => println!("Annualized real growth: {:+.2}% per year (over {:.2} years)", a, r.years),
+        None => println!("Annualized real growth: -"),
+    }
+    if r.surplus_now >= 0.0 {
+        println!("Surplus in {} money: {}", target.period, fmt_money(r.surplus_now));
+    } else {
+        println!("Shortfall in {} money: {}", target.period, fmt_money(-r.surplus_now));
     }
 
-    out.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    println!();
+    match mode {
+        Mode::Sdmx | Mode::Hybrid => println!("{}:", levels_caption(mode, ctx)),
+        Mode::Datamapper => println!("Cumulative deflator used (PCPIPCH, 1.0 at the start of {}):", series.start_period),
+    }
+    println!("  Old ({}): {:.4}", old.period, old.level);
+    println!("  New ({}): {:.4}", new.period, new.level);
+    println!(
+        "  {} ({}): {:.4}",
+        if base.is_some() { "Base" } else { "Latest" },
+        target.period,
+        target.level
+    );
+
+    print_formula(&REAL_RAISE_FORMULA);
 
-    if use_cache {
-        let _ = fs::write(&cache_file, serde_json::to_vec_pretty(&out)?);
+    if mode == Mode::Datamapper {
+        println!();
+        println!("{}", DATAMAPPER_NOTE);
     }
 
-    Ok(out)
+    if !ctx.no_jokes {
+        println!();
+        println!("{}", random_joke(-r.real_growth_pct));
+    }
+
+    Ok(())
 }
 
-// ----------------------- DataMapper: fetch PCPIPCH, return (deflator, latest_year, yearly_values) -----------------------
-async fn datamapper_deflator_and_yearly_pcpipch(
-    client: &Client,
-    cache_dir: &Path,
-    use_cache: bool,
-    country_iso3: &str,
-    start_year: i32,
-    end_year: i32,
-) -> Result<(f64, i32, Vec<YearInflation>)> {
-    let years: Vec<i32> = (start_year..=end_year).collect();
-    let periods = years
+// ----------------------- Salary history runner -----------------------
+async fn run_history(provider: &dyn CpiProvider, mode: Mode, ctx: &RunContext<'_>, query: HistoryQuery) -> Result<()> {
+    let monthly = mode != Mode::Datamapper;
+    let periods = query
+        .records
         .iter()
-        .map(|y| y.to_string())
-        .collect::<Vec<_>>()
-        .join(",");
+        .map(|r| record_period(&r.date, monthly))
+        // Quarterly and annual SDMX series: the period holding the record's month.
+        .map(|p| p.and_then(|p| if monthly { ctx.frequency.convert(&p, false) } else { Ok(p) }))
+        .collect::<Result<Vec<_>>>()?;
+    let base = query.base.as_deref().map(|b| base_range(mode, ctx.frequency, b)).transpose()?;
+
+    // One range covering every record and the base period.
+    let earliest = periods.iter().min().cloned().unwrap_or_default();
+    let range = match mode {
+        Mode::Sdmx | Mode::Hybrid => {
+            PeriodRange::with_frequency(&earliest, None, ctx.frequency).context("Invalid date range for SDMX mode")?
+        }
+        Mode::Datamapper => PeriodRange::yearly(&earliest, None).context("Invalid date range for DataMapper mode")?,
+    };
+    let range = match &base {
+        Some((_, b)) => range.including(b),
+        None => range,
+    };
 
-    let cache_key = format!("dm_{}_{}_{}_{}.json", DATAMAPPER_INDICATOR, country_iso3, start_year, end_year);
-    let cache_file = cache_dir.join(cache_key);
+    let (country_code, country_name) = select_country(provider, mode, ctx, query.country).await?;
 
-    let bytes = if use_cache { fs::read(&cache_file).ok() } else { None };
+    if ctx.verbose {
+        eprintln!("Mode: {:?} (salary history)", mode);
+        eprintln!("Country: {} ({})", country_name, country_code);
+        eprintln!("Records: {}", query.records.len());
+        eprintln!("Range: {} → {}", range.start, range.end);
+    }
 
-    let json_bytes = match bytes {
-        Some(b) => b,
-        None => {
-            let url = format!(
-                "{}/{}/{}?periods={}",
-                IMF_DATAMAPPER_BASE, DATAMAPPER_INDICATOR, country_iso3, periods
-            );
-            let resp = client
-                .get(url)
-                .send()
-                .await
-                .context("HTTP error fetching DataMapper PCPIPCH values")?;
+    let series = provider.fetch_series(&country_code, &range).await?;
+    let summary = deflate_history(&query.records, &periods, &series, base.as_ref().map(|(p, _)| p.as_str()))?;
 
-            if !resp.status().is_success() {
-                let status = resp.status();
-                let body = resp.text().await.unwrap_or_default();
-                return Err(anyhow!("DataMapper values returned {}.\nBody:\n{}", status, body));
-            }
+    if ctx.format == Format::Csv {
+        print!("{}", history_to_csv(&summary.entries));
+        return Ok(());
+    }
+    if let Some(format) = ctx.format.document() {
+        let doc = HistoryDoc {
+            format: HISTORY_FORMAT,
+            version: RESULT_VERSION,
+            mode: mode.id().to_string(),
+            country: Item { code: country_code, name: country_name },
+            source: provider.source_label().to_string(),
+            indicator: provider.indicator().to_string(),
+            result: summary,
+            segments: segments(&series),
+        };
+        return print_document(&doc, format);
+    }
 
-            let b = resp.bytes().await?.to_vec();
-            if use_cache {
-                let _ = fs::write(&cache_file, &b);
-            }
-            b
-        }
+    let (first, last) = match (summary.path.first(), summary.path.last()) {
+        (Some(f), Some(l)) => (f.period.clone(), l.period.clone()),
+        _ => return Err(anyhow!("No records")),
     };
+    print_header(&header_fields(
+        mode,
+        &country_name,
+        provider.source_label(),
+        provider.indicator(),
+        &first,
+        &last,
+    ));
+    print_sources(&series);
+    let step = if monthly { ctx.frequency.unit() } else { "year" };
+    let real_label = format!("Real ({})", summary.base.period);
+    println!("Prices of: {} (level {:.4})", summary.base.period, summary.base.level);
+    println!("Records: {}", summary.entries.len());
+    println!();
 
-    let json: Value = serde_json::from_slice(&json_bytes).context("Invalid JSON from DataMapper values")?;
-
-    let values = json
-        .get("values")
-        .ok_or_else(|| anyhow!("Unexpected DataMapper response (missing 'values')"))?;
-
-    let series = values
-        .get(DATAMAPPER_INDICATOR)
-        .and_then(|v| v.get(country_iso3))
-        .and_then(|v| v.as_object())
-        .ok_or_else(|| anyhow!("No data for {} / {}", DATAMAPPER_INDICATOR, country_iso3))?;
-
-    let mut deflator = 1.0_f64;
-    let mut latest_year: Option<i32> = None;
-    let mut yearly: Vec<YearInflation> = Vec::new();
-
-    for y in years {
-        let key = y.to_string();
-        if let Some(val) = series.get(&key) {
-            if let Some(pi) = val.as_f64() {
-                yearly.push(YearInflation { year: y, pct: pi });
-                deflator *= 1.0 + (pi / 100.0);
-                latest_year = Some(y);
-            }
-        }
+    let label_w = summary
+        .entries
+        .iter()
+        .filter_map(|e| e.label.as_deref())
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Label".len());
+    println!(
+        "{:<8} {:<lw$} {:>14} {:>10} {:>16}",
+        "Period",
+        "Label",
+        "Nominal",
+        "Level",
+        real_label,
+        lw = label_w
+    );
+    for e in &summary.entries {
+        println!(
+            "{:<8} {:<lw$} {:>14} {:>10.4} {:>16}",
+            e.period,
+            e.label.as_deref().unwrap_or("-"),
+            fmt_money(e.nominal),
+            e.level,
+            fmt_money(e.real),
+            lw = label_w
+        );
     }
 
-    let latest_year = latest_year.ok_or_else(|| anyhow!("No numeric observations found"))?;
-    Ok((deflator, latest_year, yearly))
+    println!();
+    println!("Total nominal: {}", fmt_money(summary.total_nominal));
+    println!("Total real ({} prices): {}", summary.base.period, fmt_money(summary.total_real));
+    println!(
+        "Best real {}: {} ({}, nominal {})",
+        step,
+        summary.best.period,
+        fmt_money(summary.best.real),
+        fmt_money(summary.best.nominal)
+    );
+    println!(
+        "Worst real {}: {} ({}, nominal {})",
+        step,
+        summary.worst.period,
+        fmt_money(summary.worst.real),
+        fmt_money(summary.worst.nominal)
+    );
+    if summary.path.len() > 1 {
+        let values: Vec<f64> = summary.path.iter().map(|p| p.real).collect();
+        let room = terminal_width().saturating_sub(first.len() + last.len() + 20).max(10);
+        println!("Real-value path: {} {} {}", first, plot::sparkline(&values, room), last);
+    }
+
+    print_formula(&HISTORY_FORMULA);
+
+    if mode == Mode::Datamapper {
+        println!();
+        println!("{}", DATAMAPPER_NOTE);
+    }
+
+    Ok(())
 }
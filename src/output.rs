@@ -0,0 +1,391 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::basket::BasketItem;
+use crate::compute::{adjust_amount, InflatedValue, RaiseNeeded, RealRaise, RealValue, RealValuePoint};
+use crate::decompose::Decomposition;
+use crate::history::{HistoryEntry, HistorySummary};
+use crate::series::{IndexPoint, Item, PriceIndexSeries, SeriesSegment, YearInflation};
+
+// ----------------------- Machine-readable result -----------------------
+pub const RESULT_FORMAT: &str = "rip-result";
+pub const RAISE_FORMAT: &str = "rip-raise";
+pub const HISTORY_FORMAT: &str = "rip-history";
+pub const COMPONENTS_FORMAT: &str = "rip-components";
+pub const BASKET_FORMAT: &str = "rip-basket";
+pub const DECOMPOSITION_FORMAT: &str = "rip-decomposition";
+/// Bumped on any incompatible change to [`ResultDoc`], [`RaiseDoc`],
+/// [`HistoryDoc`], [`ComponentsDoc`], [`BasketDoc`] or [`DecompositionDoc`]
+/// (renamed or removed fields).
+pub const RESULT_VERSION: u32 = 1;
+
+/// Machine-readable document format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Json,
+    Yaml,
+}
+
+/// A document emitted by `--format json|yaml`.
+pub trait Document: Serialize {
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn to_yaml(&self) -> Result<String> {
+        Ok(to_yaml(&serde_json::to_value(self)?))
+    }
+}
+
+/// Machine formats: the document alone on stdout.
+pub fn print_document(doc: &impl Document, format: DocumentFormat) -> Result<()> {
+    match format {
+        DocumentFormat::Json => println!("{}", doc.to_json()?),
+        DocumentFormat::Yaml => print!("{}", doc.to_yaml()?),
+    }
+    Ok(())
+}
+
+/// One computation, as emitted by `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultDoc {
+    pub format: &'static str,
+    pub version: u32,
+    /// "sdmx", "datamapper" or "hybrid".
+    pub mode: String,
+    /// "deflate" (old amount in today's money) or "inflate" (amount needed today).
+    pub direction: String,
+    /// DataMapper year-boundary convention: "start-inclusive",
+    /// "start-exclusive", "average-price-level" or "prorated" (`null` for SDMX).
+    pub convention: Option<String>,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    /// COICOP division of the CPI series (`--coicop`; `null` for all items).
+    pub coicop: Option<String>,
+    /// SDMX index type ("CPI", or e.g. "HICP" with `--index-type`; `null` for DataMapper).
+    pub index_type: Option<String>,
+    /// Periods as asked for ("YYYY-MM" for SDMX and hybrid, "YYYY-Qn" or
+    /// "YYYY" for quarterly or annual SDMX series, "YYYY" for DataMapper).
+    pub requested: RequestedPeriods,
+    /// Periods the source actually had data for.
+    pub used: UsedPeriods,
+    /// CPI index levels at both ends (SDMX and hybrid; `null` otherwise).
+    pub cpi: Option<CpiLevels>,
+    /// Annual PCPIPCH rates chained into the deflator (DataMapper; `null` otherwise).
+    pub pcpipch: Option<Vec<YearInflation>>,
+    /// Reference period the result is expressed in (`--base`; `null` = latest).
+    pub base: Option<IndexPoint>,
+    pub result: RealValue,
+    /// The start amount in latest prices (`--direction inflate`; `null` otherwise).
+    pub inflated: Option<InflatedValue>,
+    /// Raise needed for `--current` to break even (`null` without it).
+    pub raise: Option<RaiseNeeded>,
+    /// Every period with its real value (`--series`; `null` otherwise).
+    pub series: Option<Vec<RealValuePoint>>,
+    /// Source of each stretch of the series (hybrid mode; `null` otherwise).
+    pub segments: Option<Vec<SeriesSegment>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestedPeriods {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsedPeriods {
+    pub start: String,
+    pub latest: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CpiLevels {
+    pub start: IndexPoint,
+    pub latest: IndexPoint,
+}
+
+impl Document for ResultDoc {}
+
+/// `--raise` comparison of two amounts, as emitted by `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RaiseDoc {
+    pub format: &'static str,
+    pub version: u32,
+    /// "sdmx", "datamapper" or "hybrid".
+    pub mode: String,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    /// Price levels at the old and new dates, and the latest one.
+    pub old: IndexPoint,
+    pub new: IndexPoint,
+    pub latest: IndexPoint,
+    /// Reference period of `surplus_now` (`--base`; `null` = latest).
+    pub base: Option<IndexPoint>,
+    pub result: RealRaise,
+    /// Source of each stretch of the series (hybrid mode; `null` otherwise).
+    pub segments: Option<Vec<SeriesSegment>>,
+}
+
+impl Document for RaiseDoc {}
+
+/// `--history` file in constant prices, as emitted by `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryDoc {
+    pub format: &'static str,
+    pub version: u32,
+    /// "sdmx", "datamapper" or "hybrid".
+    pub mode: String,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    pub result: HistorySummary,
+    /// Source of each stretch of the series (hybrid mode; `null` otherwise).
+    pub segments: Option<Vec<SeriesSegment>>,
+}
+
+impl Document for HistoryDoc {}
+
+/// Several COICOP divisions side by side (`--coicop A,B,...`), as emitted
+/// by `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentsDoc {
+    pub format: &'static str,
+    pub version: u32,
+    pub mode: String,
+    pub direction: String,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    pub requested: RequestedPeriods,
+    pub components: Vec<ComponentResult>,
+}
+
+/// One COICOP division of a [`ComponentsDoc`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentResult {
+    /// Division code and name, e.g. "CP01" / "Food and non-alcoholic beverages".
+    pub coicop: Item,
+    pub used: UsedPeriods,
+    pub cpi: CpiLevels,
+    /// Reference period of the result (`--base`; `null` = latest).
+    pub base: Option<IndexPoint>,
+    pub result: RealValue,
+    /// The start amount in latest prices (`--direction inflate`; `null` otherwise).
+    pub inflated: Option<InflatedValue>,
+}
+
+impl ComponentResult {
+    /// `amount` deflated (and inflated when `inflate`) over one division's
+    /// series, to `base` or its latest level.
+    pub fn from_series(
+        coicop: &Item,
+        series: &PriceIndexSeries,
+        base: Option<IndexPoint>,
+        amount: f64,
+        inflate: bool,
+    ) -> Result<Self> {
+        let latest = series.latest().ok_or_else(|| anyhow!("No CPI data found"))?.clone();
+        let target = base.as_ref().unwrap_or(&latest).level;
+        let adjusted = adjust_amount(amount, series.base_level, target, inflate, None);
+        Ok(Self {
+            coicop: coicop.clone(),
+            used: UsedPeriods { start: series.start_period.clone(), latest: latest.period.clone() },
+            cpi: CpiLevels {
+                start: IndexPoint { period: series.start_period.clone(), level: series.base_level },
+                latest,
+            },
+            base,
+            result: adjusted.result,
+            inflated: adjusted.inflated,
+        })
+    }
+}
+
+impl Document for ComponentsDoc {}
+
+/// Personal basket index next to headline CPI (`--basket`), as emitted by
+/// `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BasketDoc {
+    pub format: &'static str,
+    pub version: u32,
+    pub mode: String,
+    pub direction: String,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    pub requested: RequestedPeriods,
+    /// Periods both indices are compared over.
+    pub used: UsedPeriods,
+    /// Divisions with their weights, normalised to sum to 1.
+    pub basket: Vec<BasketItem>,
+    /// Chain-linked personal index (100 at the start).
+    pub personal: IndexResult,
+    /// All-items CPI over the same periods.
+    pub headline: IndexResult,
+    /// Every period of the personal index with its real value (`--series`; `null` otherwise).
+    pub series: Option<Vec<RealValuePoint>>,
+}
+
+/// Result of one price index in a [`BasketDoc`].
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexResult {
+    pub levels: CpiLevels,
+    pub result: RealValue,
+    /// The start amount in latest prices (`--direction inflate`; `null` otherwise).
+    pub inflated: Option<InflatedValue>,
+}
+
+impl IndexResult {
+    /// `amount` deflated (and inflated when `inflate`) between both `levels`.
+    pub fn new(levels: CpiLevels, amount: f64, inflate: bool) -> Self {
+        let adjusted = adjust_amount(amount, levels.start.level, levels.latest.level, inflate, None);
+        Self { levels, result: adjusted.result, inflated: adjusted.inflated }
+    }
+}
+
+impl Document for BasketDoc {}
+
+/// Contribution of each COICOP division to headline inflation
+/// (`--decompose`), as emitted by `--format json|yaml`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecompositionDoc {
+    pub format: &'static str,
+    pub version: u32,
+    pub mode: String,
+    pub country: Item,
+    pub source: String,
+    pub indicator: String,
+    pub requested: RequestedPeriods,
+    pub used: UsedPeriods,
+    /// All-items CPI at both ends.
+    pub headline: CpiLevels,
+    pub result: Decomposition,
+    /// Divisions left out (no index or weight for the range).
+    pub skipped: Vec<Item>,
+}
+
+impl Document for DecompositionDoc {}
+
+// ----------------------- CSV -----------------------
+pub const SERIES_CSV_HEADER: &str = "period,level,mom_pct,yoy_pct,cumulative_pct,real,equivalent";
+
+/// Series rows as CSV (empty cells where a change is not available).
+pub fn series_to_csv(points: &[RealValuePoint]) -> String {
+    let opt = |v: Option<f64>| v.map(|x| x.to_string()).unwrap_or_default();
+    let mut out = String::from(SERIES_CSV_HEADER);
+    out.push('\n');
+    for p in points {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            p.period,
+            p.level,
+            opt(p.mom_pct),
+            opt(p.yoy_pct),
+            p.cumulative_pct,
+            p.real,
+            p.equivalent
+        ));
+    }
+    out
+}
+
+pub const HISTORY_CSV_HEADER: &str = "period,label,nominal,level,real";
+
+/// History entries as CSV (labels quoted when needed).
+pub fn history_to_csv(entries: &[HistoryEntry]) -> String {
+    let mut out = String::from(HISTORY_CSV_HEADER);
+    out.push('\n');
+    for e in entries {
+        let label = e.label.as_deref().unwrap_or_default();
+        let label = if label.contains([',', '"', '\n']) {
+            format!("\"{}\"", label.replace('"', "\"\""))
+        } else {
+            label.to_string()
+        };
+        out.push_str(&format!("{},{},{},{},{}\n", e.period, label, e.nominal, e.level, e.real));
+    }
+    out
+}
+
+// ----------------------- YAML -----------------------
+/// Block-style YAML for a JSON value, keys in document order. String values
+/// are always double-quoted with JSON escapes, which YAML accepts unchanged;
+/// identifier-like keys are left plain.
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Object(m) if !m.is_empty() => write_map(&mut out, m, 0),
+        Value::Array(a) if !a.is_empty() => write_seq(&mut out, a, 0),
+        v => {
+            out.push_str(&scalar(v));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn scalar(v: &Value) -> String {
+    match v {
+        Value::Null => "null".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        // Bool, numbers and strings print the same as in JSON.
+        v => v.to_string(),
+    }
+}
+
+fn is_block(v: &Value) -> bool {
+    match v {
+        Value::Object(m) => !m.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        _ => false,
+    }
+}
+
+fn write_map(out: &mut String, m: &serde_json::Map<String, Value>, indent: usize) {
+    let pad = " ".repeat(indent);
+    for (k, v) in m {
+        let plain = k.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !matches!(k.to_ascii_lowercase().as_str(), "true" | "false" | "null" | "yes" | "no" | "on" | "off");
+        let key = if plain {
+            k.clone()
+        } else {
+            Value::String(k.clone()).to_string()
+        };
+        match v {
+            Value::Object(inner) if is_block(v) => {
+                out.push_str(&format!("{}{}:\n", pad, key));
+                write_map(out, inner, indent + 2);
+            }
+            Value::Array(items) if is_block(v) => {
+                out.push_str(&format!("{}{}:\n", pad, key));
+                write_seq(out, items, indent + 2);
+            }
+            v => out.push_str(&format!("{}{}: {}\n", pad, key, scalar(v))),
+        }
+    }
+}
+
+fn write_seq(out: &mut String, items: &[Value], indent: usize) {
+    let pad = " ".repeat(indent);
+    for v in items {
+        match v {
+            Value::Object(inner) if is_block(v) => {
+                // First key on the "- " line, the rest aligned under it.
+                let mut nested = String::new();
+                write_map(&mut nested, inner, indent + 2);
+                out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+            }
+            Value::Array(inner) if is_block(v) => {
+                out.push_str(&format!("{}-\n", pad));
+                write_seq(out, inner, indent + 2);
+            }
+            v => out.push_str(&format!("{}- {}\n", pad, scalar(v))),
+        }
+    }
+}
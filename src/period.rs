@@ -0,0 +1,394 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use std::fmt;
+use std::str::FromStr;
+
+// ----------------------- Frequency -----------------------
+/// Period frequency of an SDMX series (its `FREQUENCY` dimension).
+///
+/// Periods are "YYYY-MM" (monthly), "YYYY-Qn" (quarterly) or "YYYY" (annual).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Frequency {
+    #[default]
+    Monthly,
+    Quarterly,
+    Annual,
+}
+
+impl Frequency {
+    /// SDMX frequency code: "M", "Q" or "A".
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Monthly => "M",
+            Self::Quarterly => "Q",
+            Self::Annual => "A",
+        }
+    }
+
+    /// Months in one period.
+    pub fn months(self) -> i32 {
+        match self {
+            Self::Monthly => 1,
+            Self::Quarterly => 3,
+            Self::Annual => 12,
+        }
+    }
+
+    /// Name of one period ("month", "quarter", "year").
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Monthly => "month",
+            Self::Quarterly => "quarter",
+            Self::Annual => "year",
+        }
+    }
+
+    /// Frequency of a period as returned by [`parse_ym`].
+    pub fn of(period: &str) -> Option<Self> {
+        match period.as_bytes() {
+            [_, _, _, _] => Some(Self::Annual),
+            [_, _, _, _, b'-', b'Q', _] => Some(Self::Quarterly),
+            [_, _, _, _, b'-', _, _] => Some(Self::Monthly),
+            _ => None,
+        }
+    }
+
+    /// The period of this frequency holding month `index` (see [`month_index`]).
+    fn period(self, index: i32) -> String {
+        let (y, m) = (index.div_euclid(12), index.rem_euclid(12));
+        match self {
+            Self::Monthly => format!("{:04}-{:02}", y, m + 1),
+            Self::Quarterly => format!("{:04}-Q{}", y, m / 3 + 1),
+            Self::Annual => format!("{:04}", y),
+        }
+    }
+
+    /// `period` (any format) as a period of this frequency: the one holding
+    /// its first month, or its last month with `last`.
+    pub fn convert(self, period: &str, last: bool) -> Result<String> {
+        let p = parse_ym(period)?;
+        let freq = Self::of(&p).ok_or_else(|| anyhow!("Invalid period '{}'", period))?;
+        let first = month_index(&p).ok_or_else(|| anyhow!("Invalid period '{}'", period))?;
+        Ok(self.period(if last { first + freq.months() - 1 } else { first }))
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "m" | "monthly" => Ok(Self::Monthly),
+            "q" | "quarterly" => Ok(Self::Quarterly),
+            "a" | "annual" => Ok(Self::Annual),
+            _ => Err(anyhow!("Unknown frequency '{}' (expected M, Q or A)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Monthly => "monthly",
+            Self::Quarterly => "quarterly",
+            Self::Annual => "annual",
+        };
+        f.write_str(s)
+    }
+}
+
+// ----------------------- Parsing helpers -----------------------
+/// Normalise a period: "YYYY-MM", "YYYY-Qn" (quarter) or "YYYY" (year).
+pub fn parse_ym(s: &str) -> Result<String> {
+    let t = s.trim();
+    let parts: Vec<&str> = t.split('-').collect();
+    let y: i32 = match (t.len(), parts.as_slice()) {
+        (4 | 7, [y, ..]) if y.len() == 4 => y.parse().map_err(|_| anyhow!("Expected YYYY-MM, YYYY-Qn or YYYY"))?,
+        _ => return Err(anyhow!("Expected YYYY-MM, YYYY-Qn or YYYY")),
+    };
+    match parts.as_slice() {
+        [_] => {
+            if !(1800..=3000).contains(&y) {
+                return Err(anyhow!("Year out of reasonable range"));
+            }
+            Ok(format!("{:04}", y))
+        }
+        [_, q] if q.starts_with(['Q', 'q']) => match q[1..].parse::<u32>() {
+            Ok(n @ 1..=4) => Ok(format!("{:04}-Q{}", y, n)),
+            _ => Err(anyhow!("Quarter out of range")),
+        },
+        [_, m] => {
+            let m: u32 = m.parse()?;
+            if !(1..=12).contains(&m) {
+                return Err(anyhow!("Month out of range"));
+            }
+            let _ = NaiveDate::from_ymd_opt(y, m, 1).ok_or_else(|| anyhow!("Invalid date"))?;
+            Ok(format!("{:04}-{:02}", y, m))
+        }
+        _ => Err(anyhow!("Expected YYYY-MM, YYYY-Qn or YYYY")),
+    }
+}
+
+pub fn parse_year_loose(s: &str) -> Result<i32> {
+    let t = s.trim();
+    let year_part = t.split('-').next().unwrap_or(t);
+    let y: i32 = year_part.parse()?;
+    if !(1800..=3000).contains(&y) {
+        return Err(anyhow!("Year out of reasonable range"));
+    }
+    Ok(y)
+}
+
+// convert "YYYY-MM" => "YYYY-MMM" (SDMX monthly format "YYYY-M01");
+// quarters ("YYYY-Q1") and years ("YYYY") are the same in SDMX
+pub fn ym_to_sdmx_period(ym: &str) -> Result<String> {
+    let t = parse_ym(ym)?;
+    match Frequency::of(&t) {
+        Some(Frequency::Monthly) => Ok(format!("{}-M{}", &t[0..4], &t[5..7])),
+        _ => Ok(t),
+    }
+}
+
+pub fn sdmx_period_to_ym(p: &str) -> String {
+    // "2025-M11" -> "2025-11", "2025-Q3" -> "2025-Q3", "2025" or "2025-A1" -> "2025"
+    // If parsing fails, return original string.
+    if p.len() == 8 && p.as_bytes()[4] == b'-' && p.as_bytes()[5] == b'M' {
+        let year = &p[0..4];
+        let mm = &p[6..8];
+        if mm.chars().all(|c| c.is_ascii_digit()) {
+            return format!("{}-{}", year, mm);
+        }
+    }
+    if p.len() == 7 && p.ends_with("-A1") {
+        return p[0..4].to_string();
+    }
+    if matches!(Frequency::of(p), Some(Frequency::Quarterly | Frequency::Annual)) {
+        if let Ok(t) = parse_ym(p) {
+            return t;
+        }
+    }
+    p.to_string()
+}
+
+/// Shift a "YYYY-MM", "YYYY-Qn" or "YYYY" period by `months` (whole
+/// quarters/years only for quarterly/annual periods); `None` for other formats.
+pub fn shift_period(p: &str, months: i32) -> Option<String> {
+    let freq = Frequency::of(p)?;
+    if months % freq.months() != 0 {
+        return None;
+    }
+    Some(freq.period(month_index(p)? + months))
+}
+
+/// Months from `from` to `to`, both of the same frequency.
+pub fn months_between(from: &str, to: &str) -> Option<i32> {
+    if Frequency::of(from)? != Frequency::of(to)? {
+        return None;
+    }
+    Some(month_index(to)? - month_index(from)?)
+}
+
+/// First month of a period as a month count since year 0.
+fn month_index(p: &str) -> Option<i32> {
+    let ym = parse_ym(p).ok()?;
+    let y: i32 = ym[0..4].parse().ok()?;
+    let m: i32 = match Frequency::of(&ym)? {
+        Frequency::Monthly => ym[5..7].parse().ok()?,
+        Frequency::Quarterly => (ym[6..7].parse::<i32>().ok()? - 1) * 3 + 1,
+        Frequency::Annual => 1,
+    };
+    Some(y * 12 + (m - 1))
+}
+
+pub fn current_ym() -> String {
+    current_period(Frequency::Monthly)
+}
+
+/// The current month, quarter or year.
+pub fn current_period(freq: Frequency) -> String {
+    let today = chrono::Utc::now().date_naive();
+    freq.period(today.year() * 12 + today.month0() as i32)
+}
+
+// ----------------------- Requested range -----------------------
+/// Inclusive period range, both ends as "YYYY-MM" (or, for quarterly and
+/// annual SDMX series, both as "YYYY-Qn" or "YYYY").
+///
+/// Yearly ranges span January of the start year to December of the end year.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodRange {
+    pub start: String,
+    pub end: String,
+}
+
+impl PeriodRange {
+    /// Monthly range; `end` defaults to (and is capped at) the current month.
+    pub fn monthly(start: &str, end: Option<&str>) -> Result<Self> {
+        Self::with_frequency(start, end, Frequency::Monthly)
+    }
+
+    /// Range of `freq` periods; `end` defaults to (and is capped at) the
+    /// current period. Inputs of another frequency are converted: a start
+    /// year begins with its first month or quarter, an end year ends with its last.
+    pub fn with_frequency(start: &str, end: Option<&str>, freq: Frequency) -> Result<Self> {
+        let start_ym = freq.convert(start, false).context("Start must be YYYY-MM, YYYY-Qn or YYYY")?;
+        let current = current_period(freq);
+
+        let mut end_ym = match end {
+            Some(s) => freq.convert(s, true).context("End must be YYYY-MM, YYYY-Qn or YYYY")?,
+            None => current.clone(),
+        };
+
+        if end_ym > current {
+            end_ym = current;
+        }
+        if end_ym < start_ym {
+            return Err(anyhow!("--end must be >= start"));
+        }
+
+        Ok(Self { start: start_ym, end: end_ym })
+    }
+
+    /// Yearly range; months in the inputs are ignored and `end` defaults to
+    /// (and is capped at) the current year.
+    pub fn yearly(start: &str, end: Option<&str>) -> Result<Self> {
+        let start_year = parse_year_loose(start).context("Start must be YYYY (or YYYY-MM)")?;
+        let current_year = chrono::Utc::now().date_naive().year();
+
+        let mut end_year = match end {
+            Some(s) => parse_year_loose(s).context("End must be YYYY (or YYYY-MM)")?,
+            None => current_year,
+        };
+
+        if end_year > current_year {
+            end_year = current_year;
+        }
+        if end_year < start_year {
+            return Err(anyhow!("--end must be >= start year"));
+        }
+
+        Ok(Self {
+            start: format!("{:04}-01", start_year),
+            end: format!("{:04}-12", end_year),
+        })
+    }
+
+    /// Like [`PeriodRange::yearly`], but keeping the months of the inputs
+    /// (January and December when absent) for pro-rating partial years. An
+    /// end capped at the current year ends in December.
+    pub fn yearly_with_months(start: &str, end: Option<&str>) -> Result<Self> {
+        let mut r = Self::yearly(start, end)?;
+        let monthly = |s: &str| parse_ym(s).ok().filter(|p| Frequency::of(p) == Some(Frequency::Monthly));
+        if let Some(ym) = monthly(start) {
+            r.start = ym;
+        }
+        if let Some(ym) = end.and_then(monthly).filter(|ym| ym[..4] == r.end[..4]) {
+            r.end = ym;
+        }
+        if r.end < r.start {
+            return Err(anyhow!("--end must be >= start"));
+        }
+        Ok(r)
+    }
+
+    /// The smallest range covering both `self` and `other`.
+    pub fn including(&self, other: &PeriodRange) -> Self {
+        Self {
+            start: self.start.clone().min(other.start.clone()),
+            end: self.end.clone().max(other.end.clone()),
+        }
+    }
+
+    /// The `months` months just before this range (for year-on-year context).
+    pub fn preceding(&self, months: i32) -> Option<Self> {
+        let step = Frequency::of(&self.start)?.months();
+        Some(Self {
+            start: shift_period(&self.start, -months)?,
+            end: shift_period(&self.start, -step)?,
+        })
+    }
+
+    pub fn start_year(&self) -> i32 {
+        parse_year_loose(&self.start).unwrap_or_default()
+    }
+
+    pub fn end_year(&self) -> i32 {
+        parse_year_loose(&self.end).unwrap_or_default()
+    }
+
+    pub fn sdmx_start(&self) -> Result<String> {
+        ym_to_sdmx_period(&self.start)
+    }
+
+    pub fn sdmx_end(&self) -> Result<String> {
+        ym_to_sdmx_period(&self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_frequencies() {
+        let q = Frequency::Quarterly;
+        assert_eq!(q.convert("2024-01", false).unwrap(), "2024-Q1");
+        assert_eq!(q.convert("2024-12", true).unwrap(), "2024-Q4");
+        assert_eq!(q.convert("2024", false).unwrap(), "2024-Q1");
+        assert_eq!(q.convert("2024", true).unwrap(), "2024-Q4");
+
+        let m = Frequency::Monthly;
+        assert_eq!(m.convert("2024-Q1", false).unwrap(), "2024-01");
+        assert_eq!(m.convert("2024-Q1", true).unwrap(), "2024-03");
+        assert_eq!(m.convert("2024-Q4", false).unwrap(), "2024-10");
+        assert_eq!(m.convert("2024-Q4", true).unwrap(), "2024-12");
+
+        let a = Frequency::Annual;
+        assert_eq!(a.convert("2024-Q4", false).unwrap(), "2024");
+        assert_eq!(a.convert("2024-Q1", true).unwrap(), "2024");
+    }
+
+    #[test]
+    fn shifts_back_across_year_boundaries() {
+        assert_eq!(shift_period("2024-01", -1).as_deref(), Some("2023-12"));
+        assert_eq!(shift_period("2024-02", -14).as_deref(), Some("2022-12"));
+        assert_eq!(shift_period("2024-Q1", -3).as_deref(), Some("2023-Q4"));
+        assert_eq!(shift_period("2024-Q2", -12).as_deref(), Some("2023-Q2"));
+        assert_eq!(shift_period("2024", -24).as_deref(), Some("2022"));
+        assert_eq!(shift_period("2023-12", 1).as_deref(), Some("2024-01"));
+    }
+
+    #[test]
+    fn shift_needs_whole_periods() {
+        assert_eq!(shift_period("2024-Q1", -1), None);
+        assert_eq!(shift_period("2024-Q1", 4), None);
+        assert_eq!(shift_period("2024", -6), None);
+        assert_eq!(shift_period("2024-M01", -1), None);
+    }
+
+    #[test]
+    fn rejects_out_of_range_periods() {
+        for p in ["2024-Q5", "2024-Q0", "2024-13", "2024-00"] {
+            assert!(parse_ym(p).is_err(), "{p}");
+            assert!(Frequency::Monthly.convert(p, false).is_err(), "{p}");
+        }
+        assert_eq!(parse_ym("2024-q3").unwrap(), "2024-Q3");
+    }
+
+    #[test]
+    fn converts_sdmx_periods() {
+        assert_eq!(sdmx_period_to_ym("2025-A1"), "2025");
+        assert_eq!(sdmx_period_to_ym("2025"), "2025");
+        assert_eq!(sdmx_period_to_ym("2025-M11"), "2025-11");
+        assert_eq!(sdmx_period_to_ym("2025-Q3"), "2025-Q3");
+        assert_eq!(ym_to_sdmx_period("2025-11").unwrap(), "2025-M11");
+        assert_eq!(ym_to_sdmx_period("2025-Q3").unwrap(), "2025-Q3");
+    }
+
+    #[test]
+    fn counts_months_between_periods_of_one_frequency() {
+        assert_eq!(months_between("2023-Q4", "2024-Q2"), Some(6));
+        assert_eq!(months_between("2024-03", "2023-12"), Some(-3));
+        assert_eq!(months_between("2024-Q1", "2024-03"), None);
+    }
+}
@@ -0,0 +1,114 @@
+// ----------------------- Terminal charts -----------------------
+// Plain Unicode text, so charts survive copy/paste into chats and slides.
+
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Value at fractional index `t` of `values`, linearly interpolated.
+fn sample(values: &[f64], t: f64) -> f64 {
+    let i = t.floor() as usize;
+    match (values.get(i), values.get(i + 1)) {
+        (Some(a), Some(b)) => a + (b - a) * (t - i as f64),
+        (Some(a), None) => *a,
+        _ => values.last().copied().unwrap_or_default(),
+    }
+}
+
+/// Resample `values` to exactly `n` columns (stretching or shrinking).
+fn resample(values: &[f64], n: usize) -> Vec<f64> {
+    if n <= 1 || values.len() <= 1 {
+        return vec![values.first().copied().unwrap_or_default(); n];
+    }
+    let step = (values.len() - 1) as f64 / (n - 1) as f64;
+    (0..n).map(|c| sample(values, c as f64 * step)).collect()
+}
+
+fn bounds(values: &[f64]) -> (f64, f64) {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    (min, max)
+}
+
+/// One character per value (at most `width`, longer series are compressed).
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    if values.is_empty() {
+        return String::new();
+    }
+    let cols = if values.len() > width { resample(values, width.max(1)) } else { values.to_vec() };
+    let (min, max) = bounds(&cols);
+    let span = max - min;
+    cols.iter()
+        .map(|v| {
+            let idx = if span > 0.0 { ((v - min) / span * 7.0).round() as usize } else { 3 };
+            SPARK_BARS[idx.min(7)]
+        })
+        .collect()
+}
+
+/// Line chart of `(period, value)` points, `width` columns wide (labels
+/// included) and `height` rows tall, with a labelled value axis and the
+/// start and latest periods marked (`●`) on the time axis.
+pub fn line_chart(y_label: &str, points: &[(String, f64)], width: usize, height: usize) -> String {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(f), Some(l)) => (f, l),
+        _ => return format!("{}: no data\n", y_label),
+    };
+    let height = height.max(3);
+    let values: Vec<f64> = points.iter().map(|(_, v)| *v).collect();
+    let (min, max) = bounds(&values);
+    let span = max - min;
+
+    let tick = |row: usize| min + span * row as f64 / (height - 1) as f64;
+    let labels: Vec<String> = (0..height).map(|r| format!("{:.2}", tick(r))).collect();
+    let label_w = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let plot_w = width.saturating_sub(label_w + 2).max(10);
+
+    // Row (0 = bottom) of every column.
+    let rows: Vec<usize> = resample(&values, plot_w)
+        .iter()
+        .map(|v| if span > 0.0 { ((v - min) / span * (height - 1) as f64).round() as usize } else { height / 2 })
+        .collect();
+
+    let mut grid = vec![vec![' '; plot_w]; height];
+    for (c, &r) in rows.iter().enumerate() {
+        let prev = if c == 0 { r } else { rows[c - 1] };
+        grid[r][c] = match r.cmp(&prev) {
+            std::cmp::Ordering::Equal => '─',
+            std::cmp::Ordering::Greater => '╱',
+            std::cmp::Ordering::Less => '╲',
+        };
+        // Steep moves: fill the rows in between.
+        for fill in grid.iter_mut().take(r.max(prev)).skip(r.min(prev) + 1) {
+            fill[c] = '│';
+        }
+    }
+    grid[rows[0]][0] = '●';
+    grid[rows[plot_w - 1]][plot_w - 1] = '●';
+
+    let mut out = format!(
+        "{} ({}: {:.2} → {}: {:.2})\n",
+        y_label, first.0, first.1, last.0, last.1
+    );
+    let mid = (height - 1) / 2;
+    for r in (0..height).rev() {
+        let (label, axis) = if r == 0 || r == mid || r == height - 1 {
+            (labels[r].as_str(), '┤')
+        } else {
+            ("", '│')
+        };
+        let line: String = grid[r].iter().collect();
+        out.push_str(&format!("{:>w$} {}{}\n", label, axis, line.trim_end(), w = label_w));
+    }
+    out.push_str(&format!("{:>w$} └{}\n", "", "─".repeat(plot_w), w = label_w));
+
+    // Time axis: start under the first column, latest under the last one.
+    let gap = plot_w.saturating_sub(first.0.chars().count() + last.0.chars().count()).max(1);
+    out.push_str(&format!(
+        "{:>w$}  {}{}{}\n",
+        "",
+        first.0,
+        " ".repeat(gap),
+        last.0,
+        w = label_w
+    ));
+    out
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::http::Retrieval;
+use crate::period::PeriodRange;
+use crate::series::{IndexPoint, Item, PriceIndexSeries};
+
+// ----------------------- Provider trait -----------------------
+/// A source of consumer price data for one country.
+///
+/// Implemented by [`crate::SdmxProvider`] (monthly CPI index levels) and
+/// [`crate::DataMapperProvider`] (annual PCPIPCH rates chained into a deflator),
+/// and [`crate::HybridProvider`] (SDMX levels, DataMapper-chained where missing).
+#[async_trait]
+pub trait CpiProvider: Send + Sync {
+    /// Human-readable source, e.g. "IMF SDMX".
+    fn source_label(&self) -> &'static str;
+
+    /// Indicator description, e.g. "CPI index level" or "PCPIPCH".
+    fn indicator(&self) -> &'static str;
+
+    /// Countries (code + name) the provider can serve.
+    async fn list_countries(&self) -> Result<Vec<Item>>;
+
+    /// Price-index series for `country` over `range`.
+    async fn fetch_series(&self, country: &str, range: &PeriodRange) -> Result<PriceIndexSeries>;
+
+    /// Every request made (and cache entry used) so far, for data provenance.
+    fn retrievals(&self) -> Vec<Retrieval>;
+}
+
+// ----------------------- Fetching with a base period -----------------------
+/// Series over `range`, plus the price level at `base` (a period of the
+/// series' format, with the range covering it). Both come from a single
+/// request covering the range and the base period, so their levels are
+/// comparable (annual deflators only are within one series).
+pub async fn fetch_with_base(
+    provider: &dyn CpiProvider,
+    country: &str,
+    range: &PeriodRange,
+    base: Option<(&str, &PeriodRange)>,
+) -> Result<(PriceIndexSeries, Option<IndexPoint>)> {
+    let Some((base_period, base_range)) = base else {
+        return Ok((provider.fetch_series(country, range).await?, None));
+    };
+    let full = provider.fetch_series(country, &range.including(base_range)).await?;
+    let point = full
+        .amount_point(base_period)
+        .ok_or_else(|| anyhow!("No price data for base period {}", base_period))?;
+
+    // Deflators are annual; index levels have the periods of the range.
+    let (start, end) = if full.is_deflator() {
+        (format!("{:04}", range.start_year()), format!("{:04}", range.end_year()))
+    } else {
+        (range.start.clone(), range.end.clone())
+    };
+    let series = full
+        .slice(&start, &end)
+        .ok_or_else(|| anyhow!("No price data between {} and {}", start, end))?;
+    Ok((series, Some(point)))
+}
@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+use crate::cache::write_atomic;
+use crate::chart::Chart;
+use crate::http::Retrieval;
+
+// ----------------------- Report model -----------------------
+/// Self-contained computation report (`--report file.html|file.md`), meant
+/// to be attached to tickets: everything, chart included, is in one file.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub title: String,
+    /// Header fields (mode, country, source, indicator, periods).
+    pub header: Vec<(String, String)>,
+    /// Computed results (nominal, real value, loss).
+    pub results: Vec<(String, String)>,
+    pub table: ReportTable,
+    /// Formula lines, shown verbatim.
+    pub formula: Vec<String>,
+    pub notes: Vec<String>,
+    pub chart: Option<Chart>,
+    pub provenance: Vec<Retrieval>,
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReportTable {
+    pub caption: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    /// Write as HTML or Markdown, chosen by the extension of `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let text = match ext.as_str() {
+            "html" | "htm" => self.to_html(),
+            "md" | "markdown" => self.to_markdown()?,
+            _ => return Err(anyhow!("Unsupported report file '{}' (use .html or .md)", path.display())),
+        };
+        write_atomic(path, text.as_bytes()).with_context(|| format!("Cannot write report {}", path.display()))
+    }
+
+    fn generated(&self) -> String {
+        format!(
+            "Generated by rip {} on {}",
+            env!("CARGO_PKG_VERSION"),
+            self.generated_at.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+
+    // ---- HTML ----
+    pub fn to_html(&self) -> String {
+        let mut h = String::new();
+        h.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        h.push_str(&format!("<title>{}</title>\n", esc(&self.title)));
+        h.push_str(
+            "<style>\n\
+             body{font-family:sans-serif;max-width:1000px;margin:2em auto;color:#222}\n\
+             table{border-collapse:collapse;margin:0.5em 0 1.5em}\n\
+             th,td{border:1px solid #ccc;padding:4px 10px;text-align:left}\n\
+             td.num{text-align:right;font-variant-numeric:tabular-nums}\n\
+             pre{background:#f5f5f5;padding:0.8em}\n\
+             .muted{color:#777;font-size:0.9em}\n\
+             </style>\n</head>\n<body>\n",
+        );
+        h.push_str(&format!("<h1>{}</h1>\n", esc(&self.title)));
+        h.push_str(&format!("<p class=\"muted\">{}</p>\n", esc(&self.generated())));
+
+        h.push_str("<h2>Query</h2>\n");
+        h.push_str(&html_fields(&self.header));
+        h.push_str("<h2>Results</h2>\n");
+        h.push_str(&html_fields(&self.results));
+
+        h.push_str(&format!("<h2>{}</h2>\n<table>\n<tr>", esc(&self.table.caption)));
+        for c in &self.table.columns {
+            h.push_str(&format!("<th>{}</th>", esc(c)));
+        }
+        h.push_str("</tr>\n");
+        for row in &self.table.rows {
+            h.push_str("<tr>");
+            for (i, cell) in row.iter().enumerate() {
+                let class = if i > 0 { " class=\"num\"" } else { "" };
+                h.push_str(&format!("<td{}>{}</td>", class, esc(cell)));
+            }
+            h.push_str("</tr>\n");
+        }
+        h.push_str("</table>\n");
+
+        h.push_str("<h2>Formula</h2>\n");
+        h.push_str(&format!("<pre>{}</pre>\n", esc(&self.formula.join("\n"))));
+        for n in &self.notes {
+            h.push_str(&format!("<p>{}</p>\n", esc(n)));
+        }
+
+        if let Some(chart) = &self.chart {
+            h.push_str("<h2>Chart</h2>\n");
+            h.push_str(&chart.to_svg());
+        }
+
+        h.push_str("<h2>Data provenance</h2>\n<table>\n<tr><th>URL</th><th>Retrieved (UTC)</th><th>Via</th></tr>\n");
+        for r in &self.provenance {
+            h.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+                esc(&r.url),
+                r.retrieved_at.format("%Y-%m-%d %H:%M:%S"),
+                esc(&via(r))
+            ));
+        }
+        h.push_str("</table>\n</body>\n</html>\n");
+        h
+    }
+
+    // ---- Markdown ----
+    pub fn to_markdown(&self) -> Result<String> {
+        let mut m = format!("# {}\n\n_{}_\n\n", self.title, self.generated());
+
+        m.push_str("## Query\n\n");
+        m.push_str(&md_fields(&self.header));
+        m.push_str("\n## Results\n\n");
+        m.push_str(&md_fields(&self.results));
+
+        m.push_str(&format!("\n## {}\n\n", self.table.caption));
+        m.push_str(&md_row(&self.table.columns));
+        m.push_str(&md_row(&vec!["---".to_string(); self.table.columns.len()]));
+        for row in &self.table.rows {
+            m.push_str(&md_row(row));
+        }
+
+        m.push_str("\n## Formula\n\n```text\n");
+        m.push_str(&self.formula.join("\n"));
+        m.push_str("\n```\n");
+        for n in &self.notes {
+            m.push_str(&format!("\n{}\n", n));
+        }
+
+        if let Some(chart) = &self.chart {
+            // PNG data URI: the document stays a single file.
+            m.push_str(&format!(
+                "\n## Chart\n\n![{}](data:image/png;base64,{})\n",
+                chart.title,
+                base64(&chart.to_png()?)
+            ));
+        }
+
+        m.push_str("\n## Data provenance\n\n| URL | Retrieved (UTC) | Via |\n| --- | --- | --- |\n");
+        for r in &self.provenance {
+            m.push_str(&format!(
+                "| `{}` | {} | {} |\n",
+                r.url,
+                r.retrieved_at.format("%Y-%m-%d %H:%M:%S"),
+                via(r)
+            ));
+        }
+        Ok(m)
+    }
+}
+
+fn via(r: &Retrieval) -> String {
+    match (r.cached, r.status) {
+        (true, _) => "local cache".to_string(),
+        (false, Some(s)) => format!("HTTP {}", s),
+        (false, None) => "HTTP (failed)".to_string(),
+    }
+}
+
+fn esc(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn html_fields(fields: &[(String, String)]) -> String {
+    let mut h = String::from("<table>\n");
+    for (k, v) in fields {
+        h.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", esc(k), esc(v)));
+    }
+    h.push_str("</table>\n");
+    h
+}
+
+fn md_fields(fields: &[(String, String)]) -> String {
+    fields.iter().map(|(k, v)| format!("- **{}:** {}\n", k, v)).collect()
+}
+
+fn md_row(cells: &[String]) -> String {
+    format!("| {} |\n", cells.iter().map(|c| c.replace('|', "\\|")).collect::<Vec<_>>().join(" | "))
+}
+
+/// Standard base64 (RFC 4648, with padding).
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,629 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::{Client, StatusCode};
+use crate::cache::{Cache, Resource};
+use crate::config::Endpoints;
+use crate::http::{HttpClient, HttpSettings, Retrieval};
+use crate::period::{current_period, sdmx_period_to_ym, ym_to_sdmx_period, Frequency, PeriodRange};
+use crate::provider::CpiProvider;
+use crate::series::{IndexPoint, Item, PriceIndexSeries};
+use crate::store::{FetchPlan, SeriesStore, StoredSeries};
+use crate::structure::{parse_structure, DataStructure, SeriesKey};
+
+// ----------------------- Constants -----------------------
+pub const IMF_SDMX_BASE: &str = "https://api.imf.org/external/sdmx/2.1";
+pub const IMF_SDMX_STRUCTURE_BASE: &str = "https://sdmxcentral.imf.org/ws/public/sdmxapi/rest";
+
+// ----------------------- SDMX CPI dataset (NEW STYLE KEY) -----------------------
+// Example:
+//   /data/CPI/RUS.CPI._T.IX.M?startPeriod=2020-M01
+// Key parts:
+//   COUNTRY.INDEX_TYPE.COICOP_1999.TYPE_OF_TRANSFORMATION.FREQUENCY
+pub const SDMX_CPI_DATASET: &str = "CPI";
+pub const SDMX_CPI_INDEX_TYPE: &str = "CPI"; // headline index family (default; e.g. "HICP")
+pub const SDMX_CPI_COICOP: &str = "_T"; // all-items (default division)
+pub const SDMX_CPI_TRANSFORMATION: &str = "IX"; // index level
+pub const SDMX_CPI_FREQ: Frequency = Frequency::Monthly; // default; "Q" and "A" also exist
+pub const SDMX_CPI_WEIGHTS_TRANSFORMATION: &str = "WGT"; // expenditure weights
+pub const SDMX_CPI_WEIGHTS_FREQ: &str = "A"; // weights are set once a year
+
+// SDMX codelists for CPI areas and COICOP divisions
+pub const SDMX_CL_AREA_CPI: &str = "CL_COUNTRY_ISO3";
+pub const SDMX_CL_COICOP: &str = "CL_COICOP_1999";
+
+// ----------------------- Client -----------------------
+pub fn build_client(settings: HttpSettings) -> Result<HttpClient> {
+    let builder = Client::builder().user_agent(concat!("rip/", env!("CARGO_PKG_VERSION"), " (rust reqwest)"));
+    HttpClient::new(builder, settings).context("Failed to build SDMX HTTP client")
+}
+
+// ----------------------- Provider -----------------------
+/// CPI index levels from the IMF SDMX `CPI` dataflow: monthly headline CPI
+/// by default, or another index type, frequency or COICOP division. With
+/// [`SdmxProvider::with_series`], any index-level series of any dataflow.
+///
+/// Clones share the HTTP client, so their requests end up in one
+/// provenance log.
+#[derive(Clone)]
+pub struct SdmxProvider {
+    client: HttpClient,
+    cache: Cache,
+    base: String,
+    structure_base: String,
+    index_type: String,
+    frequency: Frequency,
+    coicop: String,
+    dataflow: String,
+    /// Explicit series key, for every country (`with_series`).
+    key: Option<String>,
+}
+
+impl SdmxProvider {
+    pub fn new(client: HttpClient, cache: Cache) -> Self {
+        Self {
+            client,
+            cache,
+            base: IMF_SDMX_BASE.to_string(),
+            structure_base: IMF_SDMX_STRUCTURE_BASE.to_string(),
+            index_type: SDMX_CPI_INDEX_TYPE.to_string(),
+            frequency: SDMX_CPI_FREQ,
+            coicop: SDMX_CPI_COICOP.to_string(),
+            dataflow: SDMX_CPI_DATASET.to_string(),
+            key: None,
+        }
+    }
+
+    /// Fetch `series` (validated against its dataflow's DSD, see
+    /// [`DataStructure::resolve_key`]) instead of a CPI series: the country
+    /// passed to [`CpiProvider::fetch_series`] is then ignored. Ranges must
+    /// be in the periods of the series' frequency.
+    pub fn with_series(mut self, series: &SeriesKey) -> Result<Self> {
+        self.frequency = series.frequency()?;
+        self.dataflow = series.dataflow.clone();
+        self.key = Some(series.key());
+        Ok(self)
+    }
+
+    pub fn dataflow(&self) -> &str {
+        &self.dataflow
+    }
+
+    /// Whether a `with_series` series replaces the CPI.
+    pub fn is_custom(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Use another index family (e.g. "HICP") instead of the national CPI.
+    pub fn with_index_type(mut self, index_type: &str) -> Self {
+        self.index_type = index_type.to_string();
+        self
+    }
+
+    /// Fetch quarterly or annual series instead of monthly ones. Ranges passed
+    /// to [`CpiProvider::fetch_series`] must then be in that frequency's
+    /// periods (see [`PeriodRange::with_frequency`]).
+    pub fn with_frequency(mut self, frequency: Frequency) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn index_type(&self) -> &str {
+        &self.index_type
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    /// Target a COICOP division (e.g. "CP01") instead of all items.
+    pub fn with_coicop(mut self, coicop: &str) -> Self {
+        self.coicop = coicop.to_string();
+        self
+    }
+
+    pub fn coicop(&self) -> &str {
+        &self.coicop
+    }
+
+    /// Use the SDMX data/structure base URLs from `endpoints`.
+    pub fn with_endpoints(mut self, endpoints: &Endpoints) -> Self {
+        self.base = endpoints.sdmx.clone();
+        self.structure_base = endpoints.sdmx_structure.clone();
+        self
+    }
+
+    /// Weights series key of the division, e.g. "POL.CPI.CP01.WGT.A".
+    pub fn weights_key(&self, country: &str) -> String {
+        format!(
+            "{}.{}.{}.{}.{}",
+            country, self.index_type, self.coicop, SDMX_CPI_WEIGHTS_TRANSFORMATION, SDMX_CPI_WEIGHTS_FREQ
+        )
+    }
+
+    /// CPI series key, e.g. "POL.CPI._T.IX.M" (or "POL.CPI.CP01.IX.M" for a
+    /// division, "POL.HICP._T.IX.Q" for quarterly HICP); the `with_series` key if set.
+    pub fn series_key(&self, country: &str) -> String {
+        if let Some(key) = &self.key {
+            return key.clone();
+        }
+        format!(
+            "{}.{}.{}.{}.{}",
+            country,
+            self.index_type,
+            self.coicop,
+            SDMX_CPI_TRANSFORMATION,
+            self.frequency.code()
+        )
+    }
+}
+
+#[async_trait]
+impl CpiProvider for SdmxProvider {
+    fn source_label(&self) -> &'static str {
+        "IMF SDMX"
+    }
+
+    fn indicator(&self) -> &'static str {
+        if self.is_custom() {
+            "Index level"
+        } else {
+            "CPI index level"
+        }
+    }
+
+    async fn list_countries(&self) -> Result<Vec<Item>> {
+        self.load_or_fetch_countries_iso3().await
+    }
+
+    async fn fetch_series(&self, country: &str, range: &PeriodRange) -> Result<PriceIndexSeries> {
+        let Some(series) = self.fetch_index(country, range).await? else {
+            // The series key names the country and the COICOP division.
+            let not_covered = if self.key.is_none() && self.coicop != SDMX_CPI_COICOP {
+                format!("country {} or COICOP division {}", country, self.coicop)
+            } else {
+                format!("country {}", country)
+            };
+            return Err(anyhow!(
+                "No observations found for SDMX series {} between {} and {}: the series may not exist \
+                 ({} not covered) or have no data in that range",
+                self.series_key(country),
+                range.sdmx_start()?,
+                range.sdmx_end()?,
+                not_covered
+            ));
+        };
+        Ok(series)
+    }
+
+    fn retrievals(&self) -> Vec<Retrieval> {
+        self.client.retrievals()
+    }
+}
+
+// ----------------------- SDMX: fetching (cache + HTTP) -----------------------
+impl SdmxProvider {
+    // ---- index levels within a range ----
+    /// Observations of `country`'s series over `range`, as "YYYY-MM" (or
+    /// "YYYY-Qn" / "YYYY") points. Empty when the source has none (404).
+    pub async fn fetch_points(&self, country: &str, range: &PeriodRange) -> Result<Vec<IndexPoint>> {
+        let series_key = self.series_key(country);
+        let start_period = range.sdmx_start()?;
+        let end_period = range.sdmx_end()?;
+
+        let obs = self
+            .fetch_cpi_observations(&series_key, &start_period, &end_period)
+            .await?;
+
+        Ok(obs
+            .into_iter()
+            .map(|(t, v)| IndexPoint { period: sdmx_period_to_ym(&t), level: v })
+            .collect())
+    }
+
+    /// Series of `country` over `range`; `None` when the source has no
+    /// observations for it (failed requests are still errors).
+    pub async fn fetch_index(&self, country: &str, range: &PeriodRange) -> Result<Option<PriceIndexSeries>> {
+        let points = self.fetch_points(country, range).await?;
+        let Some(first) = points.first() else {
+            return Ok(None);
+        };
+
+        Ok(Some(PriceIndexSeries {
+            country: country.to_string(),
+            start_period: first.period.clone(),
+            base_level: first.level,
+            points,
+            yearly: Vec::new(),
+            segments: Vec::new(),
+        }))
+    }
+
+    // ---- fetch ISO3 country list ----
+    pub async fn load_or_fetch_countries_iso3(&self) -> Result<Vec<Item>> {
+        // Fetch ONLY the ISO3 country codelist
+        // (includes POL, RUS, CHE, USA, etc.)
+        self.load_or_fetch_codelist(SDMX_CL_AREA_CPI, "sdmx_countries_iso3.xml", "country")
+            .await
+    }
+
+    // ---- fetch COICOP division list ----
+    /// COICOP divisions of the CPI dataflow ("_T" all items, "CP01" food, ...), sorted by code.
+    pub async fn load_or_fetch_coicop(&self) -> Result<Vec<Item>> {
+        let mut items = self
+            .load_or_fetch_codelist(SDMX_CL_COICOP, "sdmx_coicop.xml", "COICOP")
+            .await?;
+        items.sort_by(|a, b| a.code.cmp(&b.code));
+        Ok(items)
+    }
+
+    // ---- fetch a dataflow's data structure definition ----
+    /// Dimensions of `dataflow` and their codelists, from the cache or
+    /// `/dataflow/IMF/{id}/latest?references=all`.
+    pub async fn load_or_fetch_structure(&self, dataflow: &str) -> Result<DataStructure> {
+        let url = format!("{}/dataflow/IMF/{}/latest?references=all", self.structure_base, dataflow);
+        let cache_name = format!("sdmx_structure_{}.xml", dataflow);
+        self.load_or_fetch_structure_message(&url, &cache_name, &format!("{} dataflow structure", dataflow), parse_structure)
+            .await
+    }
+
+    /// One SDMX Central codelist, from the cache or `/codelist/IMF/{id}/latest`.
+    async fn load_or_fetch_codelist(&self, id: &str, cache_name: &str, what: &str) -> Result<Vec<Item>> {
+        let url = format!("{}/codelist/IMF/{}/latest", self.structure_base, id);
+        self.load_or_fetch_structure_message(&url, cache_name, &format!("{} codelist", what), parse_codelist)
+            .await
+    }
+
+    /// One SDMX Central structure message, from the cache or `url`.
+    async fn load_or_fetch_structure_message<T>(
+        &self,
+        url: &str,
+        cache_name: &str,
+        what: &str,
+        parse: fn(&[u8]) -> Result<T>,
+    ) -> Result<T> {
+        if let Some(b) = self.cache.read(cache_name, Resource::Codelist) {
+            match parse(&b) {
+                Ok(parsed) => {
+                    if let Some(meta) = self.cache.meta(cache_name) {
+                        self.client.record_cached(&meta.url, meta.fetched_at);
+                    }
+                    return Ok(parsed);
+                }
+                Err(_) => self.cache.evict(cache_name),
+            }
+        }
+        self.cache.ensure_online(&format!("the SDMX {}", what))?;
+
+        let resp = self
+            .client
+            .get(url)
+            .await
+            .with_context(|| format!("HTTP error fetching SDMX Central {}", what))?
+            .error_for_status()
+            .with_context(|| format!("SDMX Central {} returned non-OK status", what))?;
+
+        // Parse before caching: an HTML error page or a truncated body must never be stored.
+        let b = resp.bytes().await?;
+        let parsed = parse(&b)?;
+        let _ = self.cache.write(cache_name, &b, url);
+        Ok(parsed)
+    }
+
+    // ---- fetch CPI observations (series store + /data SDMX-ML XML) ----
+    /// All `(TIME_PERIOD, OBS_VALUE)` pairs for `series_key` between the two
+    /// SDMX periods ("YYYY-MMM", "YYYY-Qn" or "YYYY", as the provider's
+    /// frequency), sorted by period; empty when the source has none.
+    ///
+    /// Answered from the local series store; only periods it does not cover
+    /// yet (or whose TTL expired) are downloaded.
+    pub async fn fetch_cpi_observations(
+        &self,
+        series_key: &str,
+        start_period: &str,
+        end_period: &str,
+    ) -> Result<Vec<(String, f64)>> {
+        let store = SeriesStore::new(&self.cache);
+        let source_url = format!("{}/data/{}/{}", self.base, self.dataflow, series_key);
+        let stored = store.load("sdmx", &self.dataflow, series_key);
+
+        let current = ym_to_sdmx_period(&current_period(self.frequency))?;
+        let plan = store.plan_range(stored.as_ref(), start_period, end_period, &current);
+        if plan.is_empty() {
+            let covered = stored.as_ref().and_then(|s| s.covered.clone());
+            match covered {
+                None => self
+                    .cache
+                    .ensure_online(&format!("SDMX series {} ({} → {})", series_key, start_period, end_period))?,
+                Some((cs, ce)) if start_period < cs.as_str() => self.cache.ensure_online(&format!(
+                    "SDMX series {} before {} (stored: {} → {}, requested start {})",
+                    series_key, cs, cs, ce, start_period
+                ))?,
+                Some(_) => {}
+            }
+        }
+
+        if let Some(s) = stored.as_ref().filter(|s| s.covered.is_some()) {
+            self.client.record_cached(&s.source_url, s.fetched_at);
+        }
+
+        let mut series = stored.unwrap_or_else(|| StoredSeries::new("sdmx", &self.dataflow, series_key, &source_url));
+        for fetch in &plan {
+            let obs = self.fetch_observations(&source_url, fetch).await?;
+            let range = fetch.range.as_ref().map(|(s, e)| (s.as_str(), e.as_str()));
+            series.merge(obs, range, fetch.is_full_sync());
+        }
+        if !plan.is_empty() && !series.observations.is_empty() {
+            store.save(&series)?;
+        }
+
+        Ok(series.slice(start_period, end_period))
+    }
+
+    // ---- fetch CPI weights (series store, whole series) ----
+    /// Every `(year, weight)` of the division's weights series, sorted by year
+    /// (empty when the country publishes no weights for it).
+    ///
+    /// The series is short and annual: it is always fetched whole, once its TTL expired.
+    pub async fn fetch_weights(&self, country: &str) -> Result<Vec<(String, f64)>> {
+        let key = self.weights_key(country);
+        let store = SeriesStore::new(&self.cache);
+        let source_url = format!("{}/data/{}/{}", self.base, SDMX_CPI_DATASET, key);
+        let stored = store.load("sdmx", SDMX_CPI_DATASET, &key);
+
+        // Weights are published once a year: revisions are rare.
+        let plan = store.plan_whole(stored.as_ref(), Resource::HistoricalData);
+        if stored.is_none() && plan.is_empty() {
+            self.cache.ensure_online(&format!("SDMX weights {}", key))?;
+        }
+        if let Some(s) = stored.as_ref().filter(|_| plan.is_empty()) {
+            self.client.record_cached(&s.source_url, s.fetched_at);
+        }
+
+        let mut series = stored.unwrap_or_else(|| StoredSeries::new("sdmx", SDMX_CPI_DATASET, &key, &source_url));
+        for fetch in &plan {
+            let obs = self.fetch_observations(&source_url, fetch).await?;
+            series.merge(obs, None, true);
+        }
+        if !plan.is_empty() && !series.observations.is_empty() {
+            store.save(&series)?;
+        }
+        Ok(series.observations.into_iter().collect())
+    }
+
+    /// One `/data` request:
+    ///   {source_url}?startPeriod=YYYY-MMM&endPeriod=YYYY-MMM[&updatedAfter=...]
+    /// (or YYYY-Qn / YYYY periods for quarterly / annual series)
+    async fn fetch_observations(&self, source_url: &str, fetch: &FetchPlan) -> Result<Vec<(String, f64)>> {
+        let mut params = Vec::new();
+        if let Some((start, end)) = &fetch.range {
+            params.push(format!("startPeriod={}", start));
+            params.push(format!("endPeriod={}", end));
+        }
+        if let Some(t) = fetch.updated_after {
+            params.push(format!("updatedAfter={}", t.format("%Y-%m-%dT%H:%M:%SZ")));
+        }
+        let url = if params.is_empty() {
+            source_url.to_string()
+        } else {
+            format!("{}?{}", source_url, params.join("&"))
+        };
+
+        let resp = self
+            .client
+            .get(&url)
+            .await
+            .context("HTTP error fetching SDMX data")?;
+
+        // SDMX answers 404 (NoRecordsFound) when nothing matches, e.g. no new prints yet.
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+
+        let resp = resp
+            .error_for_status()
+            .context("SDMX data returned non-OK status")?;
+        let b = resp.bytes().await?;
+        parse_observations(&b)
+    }
+}
+
+/// Parse an SDMX-ML codelist (sorted by name):
+/// `<str:Code id="POL"><com:Name xml:lang="en">Poland</com:Name></str:Code>`
+pub fn parse_codelist(xml_bytes: &[u8]) -> Result<Vec<Item>> {
+    let mut reader = Reader::from_reader(xml_bytes);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut out: Vec<Item> = Vec::new();
+
+    let mut in_code = false;
+    let mut current_id: Option<String> = None;
+    let mut current_name: Option<String> = None;
+    let mut capture_name_text = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                // quick-xml 0.31: avoid borrowing a temporary from e.name().as_ref()
+                let name = e.name().as_ref().to_vec();
+
+                if name.ends_with(b"Code") {
+                    in_code = true;
+                    current_id = None;
+                    current_name = None;
+
+                    for a in e.attributes().with_checks(false) {
+                        let a = a?;
+                        if a.key.as_ref().ends_with(b"id") {
+                            current_id = Some(a.unescape_value()?.to_string());
+                        }
+                    }
+                } else if in_code && name.ends_with(b"Name") {
+                    // Prefer xml:lang="en"
+                    let mut is_en = false;
+                    for a in e.attributes().with_checks(false) {
+                        let a = a?;
+                        if a.key.as_ref().ends_with(b"lang") {
+                            let v = a.unescape_value()?;
+                            if v.eq_ignore_ascii_case("en") {
+                                is_en = true;
+                            }
+                        }
+                    }
+
+                    // Capture if English OR if we don't have a name yet
+                    capture_name_text = is_en || current_name.is_none();
+                }
+            }
+
+            Ok(Event::Text(t)) if in_code && capture_name_text => {
+                current_name = Some(t.unescape()?.to_string());
+            }
+
+            Ok(Event::End(ref e)) => {
+                let name = e.name().as_ref().to_vec();
+
+                if name.ends_with(b"Name") {
+                    capture_name_text = false;
+                }
+
+                if name.ends_with(b"Code") && in_code {
+                    if let Some(id) = current_id.take() {
+                        let name = current_name.take().unwrap_or_else(|| id.clone());
+                        out.push(Item { code: id, name });
+                    }
+                    in_code = false;
+                }
+            }
+
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(anyhow!(e).context("Invalid SDMX Central codelist XML")),
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    if out.is_empty() {
+        return Err(anyhow!("Parsed 0 codes from SDMX Central codelist"));
+    }
+
+    out.sort_by_key(|x| x.name.to_lowercase());
+    Ok(out)
+}
+
+/// Parse `<Obs TIME_PERIOD="2020-M01" OBS_VALUE="..." .../>` elements
+/// (an empty message yields an empty list).
+pub fn parse_observations(xml_bytes: &[u8]) -> Result<Vec<(String, f64)>> {
+    let mut reader = Reader::from_reader(xml_bytes);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut obs: Vec<(String, f64)> = Vec::new();
+    let mut root_seen = false;
+    // Open elements; the body is only complete once the root is closed again.
+    let mut depth = 0usize;
+    let mut root_closed = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf);
+        match &event {
+            Ok(Event::Start(_)) => depth += 1,
+            Ok(Event::End(_)) => {
+                depth = depth.saturating_sub(1);
+                root_closed |= depth == 0;
+            }
+            // A self-closing root (`<message:StructureSpecificData .../>`): a
+            // complete message without any data.
+            Ok(Event::Empty(_)) if depth == 0 => root_closed = true,
+            _ => {}
+        }
+        match event {
+            // The root must be an SDMX data message (StructureSpecificData, GenericData, ...),
+            // not e.g. an HTML error page served with status 200.
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) if !root_seen => {
+                root_seen = true;
+                if !e.local_name().as_ref().ends_with(b"Data") {
+                    return Err(anyhow!(
+                        "Not an SDMX data message (root element <{}>)",
+                        String::from_utf8_lossy(e.name().as_ref())
+                    ));
+                }
+            }
+            // In this feed, Obs is typically <Obs .../>
+            Ok(Event::Empty(e)) | Ok(Event::Start(e)) if e.name().as_ref().ends_with(b"Obs") => {
+                let mut tp: Option<String> = None;
+                let mut val: Option<f64> = None;
+
+                for a in e.attributes().with_checks(false) {
+                    let a = a?;
+                    let k = a.key.as_ref();
+
+                    if k.ends_with(b"TIME_PERIOD") {
+                        tp = Some(a.unescape_value()?.to_string());
+                    } else if k.ends_with(b"OBS_VALUE") {
+                        let s = a.unescape_value()?;
+                        val = Some(s.parse::<f64>().context("OBS_VALUE not numeric")?);
+                    }
+                }
+
+                if let (Some(t), Some(v)) = (tp, val) {
+                    if v > 0.0 {
+                        obs.push((t, v));
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(anyhow!(e).context("Invalid SDMX XML")),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !root_seen {
+        return Err(anyhow!("Empty or truncated SDMX XML response"));
+    }
+    if !root_closed {
+        return Err(anyhow!("Truncated SDMX XML response (the data message is not closed)"));
+    }
+
+    // TIME_PERIOD sorts lexicographically for "YYYY-MMM", "YYYY-Qn" and "YYYY" formats
+    obs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(obs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USA: &[u8] = include_bytes!("../fixtures/sdmx/data/CPI/USA.CPI._T.IX.M.xml");
+
+    #[test]
+    fn parses_a_complete_data_message() {
+        let obs = parse_observations(USA).unwrap();
+        assert!(!obs.is_empty());
+        assert!(obs.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn accepts_a_self_closing_empty_data_message() {
+        let xml = br#"<?xml version="1.0" encoding="utf-8"?>
+<message:StructureSpecificData xmlns:message="http://www.sdmx.org/resources/sdmxml/schemas/v3_0/message"/>"#;
+        assert!(parse_observations(xml).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_a_truncated_data_message() {
+        // Cut right after an observation, so every element read so far is well formed.
+        let text = std::str::from_utf8(USA).unwrap();
+        let cut = text.rfind("<Obs ").unwrap();
+        let err = parse_observations(&USA[..cut]).unwrap_err();
+        assert!(err.to_string().contains("Truncated"), "{err:#}");
+
+        assert!(parse_observations(b"").is_err());
+        assert!(parse_observations(b"<html/>").is_err());
+        assert!(parse_observations(b"<html><body>Service unavailable</body></html>").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::period::shift_period;
+
+// ----------------------- Shared Types -----------------------
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    pub code: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YearInflation {
+    pub year: i32,
+    pub pct: f64,
+    /// Months of the year applied when pro-rating (`None`: the whole year).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub months: Option<u32>,
+}
+
+/// One price level at a given period ("YYYY-MM" for monthly data, "YYYY" for annual).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexPoint {
+    pub period: String,
+    pub level: f64,
+}
+
+/// Stretch of a series that came from one source (hybrid series).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesSegment {
+    pub source: String,
+    pub start: String,
+    pub end: String,
+}
+
+/// Price-index series as returned by every [`crate::CpiProvider`].
+///
+/// - SDMX: `points` are CPI index levels; `base_level` is the first one.
+/// - DataMapper: `points` are cumulative deflators at the end of each year
+///   (base 1.0 at the start of `start_period`); `yearly` keeps the PCPIPCH rates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceIndexSeries {
+    pub country: String,
+    /// Start period actually used (first period with data).
+    pub start_period: String,
+    /// Price level at `start_period`.
+    pub base_level: f64,
+    pub points: Vec<IndexPoint>,
+    pub yearly: Vec<YearInflation>,
+    /// Sources of the points, in order, when they do not all come from the
+    /// provider's own source (empty otherwise).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<SeriesSegment>,
+}
+
+impl PriceIndexSeries {
+    /// End-of-year deflators built from annual rates (DataMapper), rather than
+    /// index levels at each period (SDMX, whatever its frequency).
+    pub fn is_deflator(&self) -> bool {
+        !self.yearly.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&IndexPoint> {
+        self.points.last()
+    }
+
+    /// Price level at `period` (the base level for `start_period`).
+    pub fn level_at(&self, period: &str) -> Option<f64> {
+        if period == self.start_period {
+            return Some(self.base_level);
+        }
+        self.points.iter().find(|p| p.period == period).map(|p| p.level)
+    }
+
+    /// Period holding the price level of an amount dated `period`.
+    ///
+    /// Annual deflators are end-of-year levels: an amount of year Y is priced
+    /// at the end of Y-1 (the base level for the first year), like the start
+    /// amount of a plain run. Index levels are read at `period` itself.
+    pub fn amount_period(&self, period: &str) -> String {
+        if self.is_deflator() && period != self.start_period {
+            shift_period(period, -12).unwrap_or_default()
+        } else {
+            period.to_string()
+        }
+    }
+
+    /// Price level of an amount dated `period` (see [`Self::amount_period`]).
+    pub fn amount_point(&self, period: &str) -> Option<IndexPoint> {
+        let level = self.level_at(&self.amount_period(period))?;
+        Some(IndexPoint { period: period.to_string(), level })
+    }
+
+    /// The part of the series from `start` to `end` (inclusive, same period
+    /// format as the points), with levels unchanged so they stay comparable
+    /// with the rest of the series. `None` if nothing is left.
+    ///
+    /// Annual deflators are end-of-year levels: the base of a later start
+    /// year is the level at the end of the year before (annual SDMX index
+    /// levels are plain periods, like monthly ones).
+    pub fn slice(&self, start: &str, end: &str) -> Option<Self> {
+        let points: Vec<IndexPoint> = self
+            .points
+            .iter()
+            .filter(|p| p.period.as_str() >= start && p.period.as_str() <= end)
+            .cloned()
+            .collect();
+        let first = points.first()?;
+
+        let (start_period, base_level) = if start <= self.start_period.as_str() {
+            (self.start_period.clone(), self.base_level)
+        } else if self.is_deflator() {
+            (start.to_string(), self.level_at(&shift_period(start, -12)?)?)
+        } else {
+            (first.period.clone(), first.level)
+        };
+
+        Some(Self {
+            country: self.country.clone(),
+            start_period,
+            base_level,
+            yearly: self
+                .yearly
+                .iter()
+                .filter(|y| {
+                    let year = format!("{:04}", y.year);
+                    year.as_str() >= start && year.as_str() <= end
+                })
+                .cloned()
+                .collect(),
+            segments: self
+                .segments
+                .iter()
+                .filter(|g| g.end.as_str() >= start && g.start.as_str() <= end)
+                .map(|g| SeriesSegment {
+                    source: g.source.clone(),
+                    start: g.start.clone().max(start.to_string()),
+                    end: g.end.clone().min(end.to_string()),
+                })
+                .collect(),
+            points,
+        })
+    }
+
+    /// Inflation factor from the start to the latest point.
+    pub fn deflator(&self) -> Option<f64> {
+        self.latest().map(|p| p.level / self.base_level)
+    }
+}
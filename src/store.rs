@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::cache::{Cache, CachePolicy, Resource};
+
+// ----------------------- Stored series -----------------------
+/// One canonical observation series per (source, dataset, key), kept under
+/// `<cache>/series/<source>/<dataset>/<key>.json`. Any requested range is
+/// sliced from it locally; only missing periods are fetched.
+///
+/// Periods are kept in the source's own format ("2024-M01" for SDMX,
+/// "2024" for DataMapper), which sorts chronologically as plain strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredSeries {
+    pub source: String,
+    pub dataset: String,
+    pub key: String,
+    /// Series URL without period parameters.
+    pub source_url: String,
+    /// Inclusive period range already requested from the source (`None` = nothing yet).
+    pub covered: Option<(String, String)>,
+    /// The whole series was fetched (sources without range queries).
+    pub complete: bool,
+    /// Last time anything was fetched for this series.
+    pub fetched_at: DateTime<Utc>,
+    /// Last time the whole covered range was checked for revisions.
+    pub synced_at: DateTime<Utc>,
+    pub observations: BTreeMap<String, f64>,
+}
+
+impl StoredSeries {
+    pub fn new(source: &str, dataset: &str, key: &str, source_url: &str) -> Self {
+        let now = Utc::now();
+        Self {
+            source: source.to_string(),
+            dataset: dataset.to_string(),
+            key: key.to_string(),
+            source_url: source_url.to_string(),
+            covered: None,
+            complete: false,
+            fetched_at: now,
+            synced_at: now,
+            observations: BTreeMap::new(),
+        }
+    }
+
+    /// Observations with `start <= period <= end`, sorted by period.
+    pub fn slice(&self, start: &str, end: &str) -> Vec<(String, f64)> {
+        self.observations
+            .range(start.to_string()..=end.to_string())
+            .map(|(p, v)| (p.clone(), *v))
+            .collect()
+    }
+
+    /// Merge freshly fetched observations for `[start, end]` (`None` = whole series).
+    pub fn merge(&mut self, obs: Vec<(String, f64)>, range: Option<(&str, &str)>, full_sync: bool) {
+        let now = Utc::now();
+        match range {
+            None => {
+                self.observations = obs.into_iter().collect();
+                self.covered = match (self.observations.keys().next(), self.observations.keys().next_back()) {
+                    (Some(first), Some(last)) => Some((first.clone(), last.clone())),
+                    _ => None,
+                };
+                self.complete = true;
+            }
+            Some((start, end)) => {
+                self.observations.extend(obs);
+                self.covered = Some(match self.covered.take() {
+                    Some((cs, ce)) => (cs.min(start.to_string()), ce.max(end.to_string())),
+                    None => (start.to_string(), end.to_string()),
+                });
+            }
+        }
+
+        self.fetched_at = now;
+        if full_sync {
+            self.synced_at = now;
+        }
+    }
+}
+
+/// One request to send to the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPlan {
+    /// Period range, or `None` for the whole series.
+    pub range: Option<(String, String)>,
+    /// Only observations revised after this instant (SDMX `updatedAfter`).
+    pub updated_after: Option<DateTime<Utc>>,
+}
+
+impl FetchPlan {
+    /// Whether this request re-checks everything the store already covers.
+    pub fn is_full_sync(&self) -> bool {
+        self.range.is_none() || self.updated_after.is_some()
+    }
+}
+
+// ----------------------- Store -----------------------
+pub struct SeriesStore<'a> {
+    cache: &'a Cache,
+}
+
+impl<'a> SeriesStore<'a> {
+    pub fn new(cache: &'a Cache) -> Self {
+        Self { cache }
+    }
+
+    /// Entry name relative to the cache dir.
+    fn name(source: &str, dataset: &str, key: &str) -> String {
+        format!("series/{}/{}/{}.json", source, dataset, key)
+    }
+
+    /// Stored series, unless the policy ignores the cache. A file that no
+    /// longer parses is evicted, so the series is fetched again.
+    pub fn load(&self, source: &str, dataset: &str, key: &str) -> Option<StoredSeries> {
+        let name = Self::name(source, dataset, key);
+        let b = self.cache.read_stored(&name)?;
+        match serde_json::from_slice(&b) {
+            Ok(s) => Some(s),
+            Err(_) => {
+                self.cache.evict(&name);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, series: &StoredSeries) -> Result<()> {
+        let name = Self::name(&series.source, &series.dataset, &series.key);
+        self.cache
+            .write(&name, &serde_json::to_vec_pretty(series)?, &series.source_url)
+            .with_context(|| format!("Cannot store series {}", name))
+    }
+
+    /// Requests needed so that `stored` answers `[start, end]`.
+    ///
+    /// `current` is the current period in the series' format; it decides
+    /// which TTL applies to the tail of the series.
+    pub fn plan_range(&self, stored: Option<&StoredSeries>, start: &str, end: &str, current: &str) -> Vec<FetchPlan> {
+        let whole = || FetchPlan {
+            range: Some((start.to_string(), end.to_string())),
+            updated_after: None,
+        };
+
+        let stored = match (self.cache.policy(), stored) {
+            (CachePolicy::Offline, _) => return Vec::new(),
+            (CachePolicy::Off | CachePolicy::Refresh, _) | (CachePolicy::Use, None) => return vec![whole()],
+            (CachePolicy::Use, Some(s)) => s,
+        };
+        let (cs, ce) = match &stored.covered {
+            Some((cs, ce)) => (cs.as_str(), ce.as_str()),
+            None => return vec![whole()],
+        };
+
+        let mut plan = Vec::new();
+        let now = Utc::now();
+        let ttls = self.cache.ttls();
+
+        // Missing head: [start, cs] (one period of overlap is harmless).
+        if start < cs {
+            plan.push(FetchPlan {
+                range: Some((start.to_string(), cs.to_string())),
+                updated_after: None,
+            });
+        }
+
+        // Missing or stale tail: from the last stored observation onwards,
+        // so late prints for already-covered periods are picked up too.
+        let last = stored.observations.keys().next_back().map(|p| p.as_str()).unwrap_or(cs);
+        let tail_stale = ce >= current && age(now, stored.fetched_at) > ttls.ttl(Resource::CurrentData);
+        if end > ce || (tail_stale && end > last) {
+            plan.push(FetchPlan {
+                range: Some((last.min(ce).to_string(), end.max(ce).to_string())),
+                updated_after: None,
+            });
+        }
+
+        // Periodic revision sweep over what we already have.
+        if age(now, stored.synced_at) > ttls.ttl(Resource::HistoricalData) {
+            plan.push(FetchPlan {
+                range: Some((cs.to_string(), ce.to_string())),
+                updated_after: Some(stored.synced_at),
+            });
+        }
+
+        plan
+    }
+
+    /// Requests needed for a source that always returns the whole series.
+    pub fn plan_whole(&self, stored: Option<&StoredSeries>, resource: Resource) -> Vec<FetchPlan> {
+        let whole = vec![FetchPlan { range: None, updated_after: None }];
+        match (self.cache.policy(), stored) {
+            (CachePolicy::Offline, _) => Vec::new(),
+            (CachePolicy::Use, Some(s)) if age(Utc::now(), s.fetched_at) <= self.cache.ttls().ttl(resource) => {
+                Vec::new()
+            }
+            _ => whole,
+        }
+    }
+}
+
+fn age(now: DateTime<Utc>, then: DateTime<Utc>) -> std::time::Duration {
+    (now - then).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheTtls;
+    use chrono::Duration;
+    use std::path::PathBuf;
+
+    const CURRENT: &str = "2026-M10";
+
+    fn cache_with(policy: CachePolicy) -> Cache {
+        Cache::new(PathBuf::from("unused"), policy, CacheTtls::default())
+    }
+
+    /// Monthly observations over `[start, end]`, fetched and synced just now.
+    fn stored(start: &str, end: &str) -> StoredSeries {
+        let mut s = StoredSeries::new("sdmx", "CPI", "ITA.CPI._T.IX.M", "http://example.test/ITA.CPI._T.IX.M");
+        let obs = (2020..=2026)
+            .flat_map(|y| (1..=12).map(move |m| format!("{}-M{:02}", y, m)))
+            .filter(|p| p.as_str() >= start && p.as_str() <= end)
+            .map(|p| (p, 100.0))
+            .collect();
+        s.merge(obs, Some((start, end)), true);
+        s
+    }
+
+    fn range(start: &str, end: &str) -> FetchPlan {
+        FetchPlan {
+            range: Some((start.to_string(), end.to_string())),
+            updated_after: None,
+        }
+    }
+
+    #[test]
+    fn empty_store_fetches_the_requested_range() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        assert_eq!(store.plan_range(None, "2021-M01", "2022-M12", CURRENT), vec![range("2021-M01", "2022-M12")]);
+
+        let mut empty = stored("2021-M01", "2022-M12");
+        empty.covered = None;
+        assert_eq!(
+            store.plan_range(Some(&empty), "2021-M01", "2022-M12", CURRENT),
+            vec![range("2021-M01", "2022-M12")]
+        );
+    }
+
+    #[test]
+    fn request_inside_the_cached_range_needs_nothing() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        let s = stored("2020-M01", "2024-M06");
+        assert!(store.plan_range(Some(&s), "2021-M01", "2023-M12", CURRENT).is_empty());
+        assert!(store.plan_range(Some(&s), "2020-M01", "2024-M06", CURRENT).is_empty());
+    }
+
+    #[test]
+    fn extends_the_cached_range_on_either_side() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        let s = stored("2020-M01", "2024-M06");
+
+        assert_eq!(
+            store.plan_range(Some(&s), "2019-M01", "2022-M01", CURRENT),
+            vec![range("2019-M01", "2020-M01")]
+        );
+        assert_eq!(
+            store.plan_range(Some(&s), "2022-M01", "2025-M03", CURRENT),
+            vec![range("2024-M06", "2025-M03")]
+        );
+        assert_eq!(
+            store.plan_range(Some(&s), "2019-M01", "2025-M03", CURRENT),
+            vec![range("2019-M01", "2020-M01"), range("2024-M06", "2025-M03")]
+        );
+    }
+
+    #[test]
+    fn stale_cache_is_swept_for_revisions() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        let mut s = stored("2020-M01", "2024-M06");
+        s.synced_at -= Duration::days(8);
+
+        assert_eq!(
+            store.plan_range(Some(&s), "2021-M01", "2023-M12", CURRENT),
+            vec![FetchPlan {
+                range: Some(("2020-M01".to_string(), "2024-M06".to_string())),
+                updated_after: Some(s.synced_at),
+            }]
+        );
+    }
+
+    #[test]
+    fn stale_current_tail_is_fetched_again() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        // Covered up to the current period, but the last print is from August.
+        let mut s = stored("2020-M01", "2026-M08");
+        s.covered = Some(("2020-M01".to_string(), CURRENT.to_string()));
+        assert!(store.plan_range(Some(&s), "2021-M01", CURRENT, CURRENT).is_empty());
+
+        s.fetched_at -= Duration::hours(13);
+        assert_eq!(
+            store.plan_range(Some(&s), "2021-M01", CURRENT, CURRENT),
+            vec![range("2026-M08", CURRENT)]
+        );
+    }
+
+    #[test]
+    fn policy_overrides_the_stored_range() {
+        let s = stored("2020-M01", "2024-M06");
+        let offline = cache_with(CachePolicy::Offline);
+        assert!(SeriesStore::new(&offline).plan_range(Some(&s), "2019-M01", "2025-M03", CURRENT).is_empty());
+
+        let refresh = cache_with(CachePolicy::Refresh);
+        assert_eq!(
+            SeriesStore::new(&refresh).plan_range(Some(&s), "2021-M01", "2023-M12", CURRENT),
+            vec![range("2021-M01", "2023-M12")]
+        );
+    }
+
+    #[test]
+    fn whole_series_is_refetched_once_its_ttl_expires() {
+        let cache = cache_with(CachePolicy::Use);
+        let store = SeriesStore::new(&cache);
+        let whole = vec![FetchPlan { range: None, updated_after: None }];
+        assert_eq!(store.plan_whole(None, Resource::CurrentData), whole);
+
+        let mut s = stored("2020-M01", "2024-M06");
+        assert!(store.plan_whole(Some(&s), Resource::CurrentData).is_empty());
+
+        s.fetched_at -= Duration::hours(13);
+        assert_eq!(store.plan_whole(Some(&s), Resource::CurrentData), whole);
+        assert!(store.plan_whole(Some(&s), Resource::HistoricalData).is_empty());
+
+        let offline = cache_with(CachePolicy::Offline);
+        assert!(SeriesStore::new(&offline).plan_whole(None, Resource::CurrentData).is_empty());
+    }
+}
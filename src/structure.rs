@@ -0,0 +1,329 @@
+use anyhow::{anyhow, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+
+use crate::period::Frequency;
+use crate::sdmx::SDMX_CPI_TRANSFORMATION;
+use crate::series::Item;
+
+// ----------------------- Data structure definition -----------------------
+/// Dimensions of a dataflow, from its data structure definition (DSD).
+#[derive(Debug, Clone)]
+pub struct DataStructure {
+    /// DSD id, e.g. "DSD_PPI".
+    pub id: String,
+    /// Series dimensions in key order (the time dimension excluded).
+    pub dimensions: Vec<Dimension>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dimension {
+    pub id: String,
+    /// Codelist the dimension is enumerated by, if any.
+    pub codelist: Option<String>,
+    /// Its codes (`None` when the message does not include the codelist).
+    pub codes: Option<Vec<Item>>,
+}
+
+/// A fully specified series key: one code per dimension, in key order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesKey {
+    pub dataflow: String,
+    /// `(dimension id, code)` pairs.
+    pub values: Vec<(String, String)>,
+}
+
+impl SeriesKey {
+    /// Dot-separated key, e.g. "ITA.PPI.IX.M".
+    pub fn key(&self) -> String {
+        self.values.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>().join(".")
+    }
+
+    /// Code of the first dimension among `ids` present in the key.
+    fn value_of(&self, ids: &[&str]) -> Option<&str> {
+        ids.iter()
+            .find_map(|id| self.values.iter().find(|(d, _)| d.eq_ignore_ascii_case(id)))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Area code of the series (`COUNTRY` or `REF_AREA` dimension).
+    pub fn country(&self) -> Option<&str> {
+        self.value_of(&["COUNTRY", "REF_AREA"])
+    }
+
+    /// Transformation code (`TYPE_OF_TRANSFORMATION` dimension, or `UNIT`
+    /// where a dataflow has none; "IX" is an index level).
+    pub fn transformation(&self) -> Option<&str> {
+        self.value_of(&["TYPE_OF_TRANSFORMATION", "TRANSFORMATION", "UNIT", "UNIT_MEASURE"])
+    }
+
+    /// Whether the series holds index levels, the only thing RIP can deflate with.
+    pub fn is_index_level(&self) -> bool {
+        self.transformation()
+            .is_some_and(|t| t.eq_ignore_ascii_case(SDMX_CPI_TRANSFORMATION) || t.eq_ignore_ascii_case("INDEX"))
+    }
+
+    /// Period format of the series, from its `FREQUENCY` (or `FREQ`) dimension.
+    pub fn frequency(&self) -> Result<Frequency> {
+        let code = self.value_of(&["FREQUENCY", "FREQ"]).ok_or_else(|| {
+            anyhow!("Dataflow {} has no FREQUENCY dimension: cannot tell the period format", self.dataflow)
+        })?;
+        code.parse()
+    }
+}
+
+impl DataStructure {
+    /// Check `spec` against the DSD and return the series key it names.
+    ///
+    /// `spec` is either a dot-separated key ("ITA.PPI.IX.M") or
+    /// `DIMENSION=CODE` pairs separated by commas, in any order
+    /// ("COUNTRY=ITA,FREQUENCY=M,..."). Every dimension needs exactly one
+    /// code, enumerated dimensions only accept codes of their codelist, and
+    /// the series must be an index level (see [`SeriesKey::is_index_level`]).
+    pub fn resolve_key(&self, dataflow: &str, spec: &str) -> Result<SeriesKey> {
+        let spec = spec.trim();
+        let dims: Vec<&str> = self.dimensions.iter().map(|d| d.id.as_str()).collect();
+
+        let raw: Vec<String> = if spec.contains('=') {
+            let mut given: HashMap<usize, String> = HashMap::new();
+            for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (dim, code) = pair
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Expected DIMENSION=CODE, got '{}'", pair))?;
+                let idx = dims
+                    .iter()
+                    .position(|d| d.eq_ignore_ascii_case(dim.trim()))
+                    .ok_or_else(|| {
+                        anyhow!("Unknown dimension '{}' for dataflow {} (dimensions: {})", dim.trim(), dataflow, dims.join(", "))
+                    })?;
+                if given.insert(idx, code.trim().to_string()).is_some() {
+                    return Err(anyhow!("Dimension {} given twice", dims[idx]));
+                }
+            }
+            let missing: Vec<&str> = (0..dims.len()).filter(|i| !given.contains_key(i)).map(|i| dims[i]).collect();
+            if !missing.is_empty() {
+                return Err(anyhow!(
+                    "Missing dimensions {} (one code per dimension selects a single series)",
+                    missing.join(", ")
+                ));
+            }
+            (0..dims.len()).map(|i| given.remove(&i).unwrap_or_default()).collect()
+        } else {
+            let parts: Vec<String> = spec.split('.').map(|p| p.trim().to_string()).collect();
+            if parts.len() != dims.len() {
+                return Err(anyhow!(
+                    "Key '{}' has {} parts, but dataflow {} has {} dimensions ({})",
+                    spec,
+                    parts.len(),
+                    dataflow,
+                    dims.len(),
+                    dims.join(".")
+                ));
+            }
+            parts
+        };
+
+        let mut values = Vec::with_capacity(raw.len());
+        for (dim, code) in self.dimensions.iter().zip(raw) {
+            if code.is_empty() || code.contains('+') {
+                return Err(anyhow!(
+                    "Dimension {} needs exactly one code (got '{}'): RIP deflates with a single series",
+                    dim.id,
+                    code
+                ));
+            }
+            let code = match &dim.codes {
+                Some(codes) => codes
+                    .iter()
+                    .find(|c| c.code.eq_ignore_ascii_case(&code))
+                    .map(|c| c.code.clone())
+                    .ok_or_else(|| {
+                        let some: Vec<&str> = codes.iter().take(10).map(|c| c.code.as_str()).collect();
+                        anyhow!(
+                            "'{}' is not a code of dimension {} (codelist {}: {}{})",
+                            code,
+                            dim.id,
+                            dim.codelist.as_deref().unwrap_or("-"),
+                            some.join(", "),
+                            if codes.len() > some.len() { ", ..." } else { "" }
+                        )
+                    })?,
+                None => code,
+            };
+            values.push((dim.id.clone(), code));
+        }
+
+        let key = SeriesKey { dataflow: dataflow.to_string(), values };
+        if !key.is_index_level() {
+            return Err(match key.transformation() {
+                Some(t) => anyhow!(
+                    "{} is a {} series, not an index level ({}): it cannot deflate amounts",
+                    key.key(),
+                    t,
+                    SDMX_CPI_TRANSFORMATION
+                ),
+                None => anyhow!(
+                    "Dataflow {} has no TYPE_OF_TRANSFORMATION or UNIT dimension: cannot tell whether {} is an index level",
+                    dataflow,
+                    key.key()
+                ),
+            });
+        }
+        Ok(key)
+    }
+}
+
+// ----------------------- SDMX-ML structure parsing -----------------------
+/// Parse a structure message holding a DSD and, optionally, its codelists
+/// (`/dataflow/{agency}/{id}/latest?references=all`).
+///
+/// `<str:Dimension id="COUNTRY" position="1">...<str:Enumeration><Ref id="CL_..."/>`
+pub fn parse_structure(xml_bytes: &[u8]) -> Result<DataStructure> {
+    let mut reader = Reader::from_reader(xml_bytes);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut dsd_id: Option<String> = None;
+    // (id, position, codelist)
+    let mut dims: Vec<(String, usize, Option<String>)> = Vec::new();
+    let mut codelists: HashMap<String, Vec<Item>> = HashMap::new();
+
+    let mut in_dimension = false;
+    let mut in_enumeration = false;
+    let mut codelist: Option<String> = None;
+    let mut code: Option<Item> = None;
+    let mut capture_name_text = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            // A code without a name: `<str:Code id="X"/>`.
+            Ok(Event::Empty(ref e)) if codelist.is_some() && e.local_name().as_ref() == b"Code" => {
+                for a in e.attributes().with_checks(false) {
+                    let a = a?;
+                    if a.key.local_name().as_ref() == b"id" {
+                        let id = a.unescape_value()?.to_string();
+                        if let Some(cl) = &codelist {
+                            codelists.entry(cl.clone()).or_default().push(Item { name: id.clone(), code: id });
+                        }
+                    }
+                }
+            }
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                let name = e.local_name().as_ref().to_vec();
+                let attr = |key: &[u8]| -> Result<Option<String>> {
+                    for a in e.attributes().with_checks(false) {
+                        let a = a?;
+                        if a.key.local_name().as_ref() == key {
+                            return Ok(Some(a.unescape_value()?.to_string()));
+                        }
+                    }
+                    Ok(None)
+                };
+
+                match name.as_slice() {
+                    b"DataStructure" if dsd_id.is_none() => dsd_id = attr(b"id")?,
+                    b"Dimension" => {
+                        in_dimension = true;
+                        let id = attr(b"id")?.ok_or_else(|| anyhow!("Dimension without id"))?;
+                        let position = attr(b"position")?.and_then(|p| p.parse().ok()).unwrap_or(dims.len() + 1);
+                        dims.push((id, position, None));
+                    }
+                    b"Enumeration" if in_dimension => in_enumeration = true,
+                    b"Ref" if in_enumeration => {
+                        if let Some(d) = dims.last_mut() {
+                            d.2 = attr(b"id")?;
+                        }
+                    }
+                    b"Codelist" => codelist = attr(b"id")?,
+                    b"Code" if codelist.is_some() => {
+                        code = attr(b"id")?.map(|id| Item { name: id.clone(), code: id });
+                    }
+                    b"Name" if code.is_some() => {
+                        // Prefer xml:lang="en"; otherwise keep the first name.
+                        let en = attr(b"lang")?.is_some_and(|l| l.eq_ignore_ascii_case("en"));
+                        capture_name_text = en || code.as_ref().is_some_and(|c| c.name == c.code);
+                    }
+                    _ => {}
+                }
+            }
+
+            Ok(Event::Text(t)) if capture_name_text => {
+                if let Some(c) = code.as_mut() {
+                    c.name = t.unescape()?.to_string();
+                }
+            }
+
+            Ok(Event::End(ref e)) => match e.local_name().as_ref() {
+                b"Name" => capture_name_text = false,
+                b"Dimension" => in_dimension = false,
+                b"Enumeration" => in_enumeration = false,
+                b"Code" => {
+                    if let (Some(cl), Some(c)) = (&codelist, code.take()) {
+                        codelists.entry(cl.clone()).or_default().push(c);
+                    }
+                }
+                b"Codelist" => codelist = None,
+                _ => {}
+            },
+
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(anyhow!(e).context("Invalid SDMX structure XML")),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let id = dsd_id.ok_or_else(|| anyhow!("No data structure definition in SDMX structure message"))?;
+    if dims.is_empty() {
+        return Err(anyhow!("Data structure {} has no dimensions", id));
+    }
+    dims.sort_by_key(|d| d.1);
+
+    Ok(DataStructure {
+        id,
+        dimensions: dims
+            .into_iter()
+            .map(|(id, _, codelist)| Dimension {
+                codes: codelist.as_ref().and_then(|cl| codelists.get(cl).cloned()),
+                id,
+                codelist,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ppi() -> DataStructure {
+        parse_structure(include_bytes!("../fixtures/structure/dataflow/IMF/PPI/latest.xml")).unwrap()
+    }
+
+    #[test]
+    fn resolves_dotted_and_named_keys() {
+        let dsd = ppi();
+        let dotted = dsd.resolve_key("PPI", "ita.PPI.IX.M").unwrap();
+        assert_eq!(dotted.key(), "ITA.PPI.IX.M");
+        assert_eq!(dotted.country(), Some("ITA"));
+        assert_eq!(dotted.frequency().unwrap(), Frequency::Monthly);
+
+        let named = dsd
+            .resolve_key("PPI", "FREQUENCY=M, TYPE_OF_TRANSFORMATION=IX, COUNTRY=ITA, INDICATOR=PPI")
+            .unwrap();
+        assert_eq!(named, dotted);
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        let dsd = ppi();
+        let err = |spec: &str| dsd.resolve_key("PPI", spec).unwrap_err().to_string();
+
+        assert!(err("ITA.PPI.PC_PP_PT.M").contains("not an index level"));
+        assert!(err("ITA.PPI.IX").contains("has 3 parts"));
+        assert!(err("FRA.PPI.IX.M").contains("not a code of dimension COUNTRY"));
+        assert!(err("ITA.PPI+PPI_MFG.IX.M").contains("exactly one code"));
+        assert!(err("COUNTRY=ITA,INDICATOR=PPI,FREQUENCY=M").contains("Missing dimensions TYPE_OF_TRANSFORMATION"));
+    }
+}